@@ -0,0 +1,349 @@
+//! ゲストのインポート・エクスポートの型が、ホストの期待と合っているかの確認。
+//!
+//! wasm を作り直したときに `context_render` などのシグネチャが変わっても、
+//! wasmtime のエラーは「型が合わない」としか言わない。[`inspect`] はインスタンス化の前に
+//! インポートとエクスポートを 1 つずつ調べ、[`check`] はどれがどう食い違っているかを報告する。
+
+use std::fmt;
+
+use wasmtime::{ExternType, FuncType, Module};
+
+use crate::bindgen;
+use crate::fonts::REGISTER_EXPORTS;
+use crate::resource::{HOST_FUNCTIONS, HOST_MODULE};
+use crate::RenderError;
+
+/// `context_render` のシグネチャ (詳細は [`RenderOptions`](crate::RenderOptions) のモジュールを参照)。
+pub const CONTEXT_RENDER: &str = "(i32, i32, i32, i32, i32, f64, i32, i32, i32, i32) -> i32";
+
+/// ホストが使うエクスポート 1 つ分。
+#[derive(Debug, Clone, Copy)]
+pub struct ExpectedExport {
+    pub name: &'static str,
+    /// 受け付けるシグネチャ (どれか 1 つに合えばよい)。`memory` は空
+    pub signatures: &'static [&'static str],
+    /// なくてもよいか
+    pub optional: bool,
+    /// これがあれば、代わりになくてもよいエクスポート
+    pub alternative: Option<&'static str>,
+}
+
+/// ホストが使うエクスポートの一覧。
+pub const EXPECTED_EXPORTS: &[ExpectedExport] = &[
+    ExpectedExport {
+        name: "memory",
+        signatures: &[],
+        optional: false,
+        alternative: None,
+    },
+    ExpectedExport {
+        name: "context_render",
+        signatures: &[CONTEXT_RENDER],
+        optional: false,
+        alternative: None,
+    },
+    ExpectedExport {
+        name: "__wbindgen_malloc",
+        signatures: &["(i32) -> i32", "(i32, i32) -> i32"],
+        optional: false,
+        alternative: Some("__wbindgen_realloc"),
+    },
+    ExpectedExport {
+        name: "__wbindgen_realloc",
+        signatures: &["(i32, i32, i32) -> i32", "(i32, i32, i32, i32) -> i32"],
+        optional: true,
+        alternative: None,
+    },
+    ExpectedExport {
+        name: "__wbindgen_free",
+        signatures: &["(i32, i32) -> ()", "(i32, i32, i32) -> ()"],
+        optional: true,
+        alternative: None,
+    },
+    ExpectedExport {
+        name: "context_new",
+        signatures: &["(i32, i32, i32, i32) -> i32"],
+        optional: true,
+        alternative: None,
+    },
+    ExpectedExport {
+        name: "context_free",
+        signatures: &["(i32) -> ()"],
+        optional: true,
+        alternative: None,
+    },
+];
+
+/// フォント登録関数 ([`REGISTER_EXPORTS`]) のシグネチャ。
+const REGISTER_SIGNATURES: &[&str] = &["(i32, i32) -> i32", "(i32, i32) -> ()"];
+
+/// インポート・エクスポート 1 つの状態。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// ホストの期待どおり
+    Ok,
+    /// インポート: ホストに実装がなく、呼ばれた時点でエラーを返すスタブにつなぐ
+    Stub,
+    /// エクスポート: ホストは使わない
+    Unused,
+    /// なくてもよいエクスポートがない
+    Absent,
+    /// 必須のエクスポートがない
+    Missing,
+    /// 型が違う。期待する型を持つ
+    Mistyped(String),
+    /// インポート: ホストが提供しないので、インスタンス化できない
+    Unresolved,
+}
+
+impl Status {
+    /// インスタンス化やレンダリングが失敗する状態か。
+    pub fn is_problem(&self) -> bool {
+        matches!(
+            self,
+            Status::Missing | Status::Mistyped(_) | Status::Unresolved
+        )
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "ok",
+            Status::Stub => "stub",
+            Status::Unused => "unused",
+            Status::Absent => "absent",
+            Status::Missing => "MISSING",
+            Status::Mistyped(_) => "MISTYPED",
+            Status::Unresolved => "UNRESOLVED",
+        })
+    }
+}
+
+/// インポート・エクスポート 1 つ分の検査結果。
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// インポート元のモジュール名 (エクスポートは `None`)
+    pub module: Option<String>,
+    pub name: String,
+    /// wasm の型 ([`extern_type`] の形式)。ないエクスポートは `None`
+    pub ty: Option<String>,
+    pub status: Status,
+}
+
+impl Entry {
+    /// `module::name` (エクスポートは `name`)。
+    pub fn qualified_name(&self) -> String {
+        match &self.module {
+            Some(module) => format!("{module}::{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// 問題があれば、その説明。
+    pub fn problem(&self) -> Option<String> {
+        let name = self.qualified_name();
+        let ty = self.ty.as_deref().unwrap_or("?");
+        match &self.status {
+            Status::Missing if self.module.is_none() => {
+                let alternative = EXPECTED_EXPORTS
+                    .iter()
+                    .find(|e| e.name == self.name)
+                    .and_then(|e| e.alternative);
+                Some(match alternative {
+                    Some(alternative) => {
+                        format!("missing export `{name}` (or `{alternative}`)")
+                    }
+                    None => format!("missing export `{name}`"),
+                })
+            }
+            Status::Mistyped(expected) if self.module.is_none() && expected == "memory" => {
+                Some(format!("export `{name}` is not a memory"))
+            }
+            Status::Mistyped(expected) if self.ty.as_deref().is_some_and(is_signature) => {
+                Some(format!("`{name}` has signature {ty}, expected {expected}"))
+            }
+            Status::Mistyped(expected) if self.module.is_none() => Some(format!(
+                "export `{name}` is not a function (expected {expected})"
+            )),
+            Status::Mistyped(expected) => Some(format!(
+                "import `{name}` has type {ty}, the host provides {expected}"
+            )),
+            Status::Unresolved => Some(format!(
+                "import `{name}` ({ty}) is not provided by the host"
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// [`inspect`] の結果。
+#[derive(Debug, Clone, Default)]
+pub struct Inspection {
+    /// モジュールの並び順のインポート
+    pub imports: Vec<Entry>,
+    /// モジュールの並び順のエクスポートと、その後ろにない [`EXPECTED_EXPORTS`]
+    pub exports: Vec<Entry>,
+}
+
+impl Inspection {
+    /// 問題の説明をすべて列挙する。
+    pub fn problems(&self) -> Vec<String> {
+        self.exports
+            .iter()
+            .chain(&self.imports)
+            .filter_map(Entry::problem)
+            .collect()
+    }
+
+    /// 問題がないか。
+    pub fn is_ok(&self) -> bool {
+        self.imports
+            .iter()
+            .chain(&self.exports)
+            .all(|e| !e.status.is_problem())
+    }
+}
+
+/// `(i32, f64) -> i32` の形の文字列にする。
+pub fn signature(ty: &FuncType) -> String {
+    let join = |types: &mut dyn Iterator<Item = wasmtime::ValType>| {
+        types.map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+    };
+    let params = join(&mut ty.params());
+    let results = ty.results().collect::<Vec<_>>();
+    match results.as_slice() {
+        [] => format!("({params}) -> ()"),
+        [result] => format!("({params}) -> {result}"),
+        _ => format!("({params}) -> ({})", join(&mut results.into_iter())),
+    }
+}
+
+fn is_signature(ty: &str) -> bool {
+    ty.starts_with('(')
+}
+
+/// 関数なら [`signature`]、それ以外は `memory 17..` や `global mut i32` の形の文字列にする。
+pub fn extern_type(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Func(ty) => signature(ty),
+        ExternType::Memory(ty) => {
+            let index = if ty.is_64() { " i64" } else { "" };
+            let shared = if ty.is_shared() { " shared" } else { "" };
+            match ty.maximum() {
+                Some(max) => format!("memory{index}{shared} {}..{max} pages", ty.minimum()),
+                None => format!("memory{index}{shared} {}.. pages", ty.minimum()),
+            }
+        }
+        ExternType::Table(ty) => match ty.maximum() {
+            Some(max) => format!("table {} {}..{max}", ty.element(), ty.minimum()),
+            None => format!("table {} {}..", ty.element(), ty.minimum()),
+        },
+        ExternType::Global(ty) => match ty.mutability() {
+            wasmtime::Mutability::Var => format!("global mut {}", ty.content()),
+            wasmtime::Mutability::Const => format!("global {}", ty.content()),
+        },
+    }
+}
+
+/// `module` のインポートとエクスポートを、ホストの期待と照らし合わせる。
+pub fn inspect(module: &Module) -> Inspection {
+    let imports = module
+        .imports()
+        .map(|import| {
+            let ty = import.ty();
+            let status = import_status(module, import.module(), import.name(), &ty);
+            Entry {
+                module: Some(import.module().to_string()),
+                name: import.name().to_string(),
+                ty: Some(extern_type(&ty)),
+                status,
+            }
+        })
+        .collect();
+
+    let has = |name: &str| module.exports().any(|e| e.name() == name);
+    let mut exports: Vec<Entry> = module
+        .exports()
+        .map(|export| {
+            let ty = export.ty();
+            Entry {
+                module: None,
+                name: export.name().to_string(),
+                ty: Some(extern_type(&ty)),
+                status: export_status(export.name(), &ty),
+            }
+        })
+        .collect();
+    for expected in EXPECTED_EXPORTS.iter().filter(|e| !has(e.name)) {
+        let satisfied = expected.alternative.is_some_and(has);
+        exports.push(Entry {
+            module: None,
+            name: expected.name.to_string(),
+            ty: None,
+            status: if expected.optional || satisfied {
+                Status::Absent
+            } else {
+                Status::Missing
+            },
+        });
+    }
+
+    Inspection { imports, exports }
+}
+
+fn import_status(wasm: &Module, module: &str, name: &str, ty: &ExternType) -> Status {
+    let Some(func) = ty.func() else {
+        return Status::Unresolved;
+    };
+    if module == HOST_MODULE {
+        return match HOST_FUNCTIONS.iter().find(|(n, _)| *n == name) {
+            Some((_, expected)) if signature(func) == *expected => Status::Ok,
+            Some((_, expected)) => Status::Mistyped(expected.to_string()),
+            None => Status::Unresolved,
+        };
+    }
+    match bindgen::provides(wasm, module, name, func) {
+        Some(true) => Status::Ok,
+        Some(false) => Status::Stub,
+        None => Status::Unresolved,
+    }
+}
+
+fn export_status(name: &str, ty: &ExternType) -> Status {
+    let signatures = match EXPECTED_EXPORTS.iter().find(|e| e.name == name) {
+        Some(expected) if expected.signatures.is_empty() => {
+            return match ty {
+                ExternType::Memory(_) => Status::Ok,
+                _ => Status::Mistyped("memory".to_string()),
+            };
+        }
+        Some(expected) => expected.signatures,
+        None if REGISTER_EXPORTS.contains(&name) => REGISTER_SIGNATURES,
+        None => return Status::Unused,
+    };
+    match ty.func().map(signature) {
+        Some(actual) if signatures.contains(&actual.as_str()) => Status::Ok,
+        _ => Status::Mistyped(signatures.join(" or ")),
+    }
+}
+
+/// `module` がホストとつなげるかを調べる。
+///
+/// 食い違いがあれば、すべてを列挙したエラーを返す。
+pub fn check(module: &Module) -> Result<(), RenderError> {
+    let problems = inspect(module).problems();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(RenderError::Abi(problems))
+}
+
+/// `name` のエクスポートに期待するシグネチャ (`A or B` の形)。
+pub(crate) fn expected(name: &str) -> String {
+    EXPECTED_EXPORTS
+        .iter()
+        .find(|e| e.name == name)
+        .map(|e| e.signatures.join(" or "))
+        .unwrap_or_else(|| "?".to_string())
+}
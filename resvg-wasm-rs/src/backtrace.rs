@@ -0,0 +1,122 @@
+//! トラップしたときのゲストのコールスタック。
+//!
+//! wasmtime がエラーに添える `WasmBacktrace` を、ホストから扱いやすい形に写す。
+//! 関数名は name セクションから取り、[`EngineOptions::debug_info`](crate::EngineOptions)
+//! が有効ならゲストの DWARF からソースの位置も引く。
+
+use std::fmt;
+
+use wasmtime::{FrameInfo, WasmBacktrace};
+
+/// ゲストのコールスタック。`frames` はトラップした関数から呼び出し元へ向かって並ぶ。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuestBacktrace {
+    pub frames: Vec<GuestFrame>,
+}
+
+/// コールスタックの 1 段。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestFrame {
+    pub func_index: u32,
+    /// name セクションの関数名 (Rust の関数名はデマングル済み)
+    pub func_name: Option<String>,
+    /// モジュールの先頭から数えた命令のオフセット
+    pub module_offset: Option<usize>,
+    /// DWARF から引いたソースの位置。インライン展開された関数があれば内側から順に並ぶ。
+    /// デバッグ情報がなければ空
+    pub locations: Vec<SourceLocation>,
+}
+
+/// ソースコード上の位置。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl GuestBacktrace {
+    /// `error` に添えられたバックトレースを取り出す。なければ `None`。
+    pub(crate) fn from_error(error: &wasmtime::Error) -> Option<Self> {
+        let backtrace = error.downcast_ref::<WasmBacktrace>()?;
+        Some(GuestBacktrace {
+            frames: backtrace.frames().iter().map(GuestFrame::new).collect(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl GuestFrame {
+    fn new(frame: &FrameInfo) -> Self {
+        GuestFrame {
+            func_index: frame.func_index(),
+            func_name: frame.func_name().map(demangle),
+            module_offset: frame.module_offset(),
+            locations: frame
+                .symbols()
+                .iter()
+                .map(|symbol| SourceLocation {
+                    function: symbol.name().map(demangle),
+                    file: symbol.file().map(str::to_string),
+                    line: symbol.line(),
+                    column: symbol.column(),
+                })
+                .collect(),
+        }
+    }
+
+    /// 表示する関数名。ソースの位置に名前があればそれを優先する。
+    pub fn name(&self) -> String {
+        self.locations
+            .first()
+            .and_then(|location| location.function.clone())
+            .or_else(|| self.func_name.clone())
+            .unwrap_or_else(|| format!("<wasm function {}>", self.func_index))
+    }
+}
+
+impl fmt::Display for GuestBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{i:>4}: ")?;
+            if let Some(offset) = frame.module_offset {
+                write!(f, "{offset:#8x} - ")?;
+            }
+            write!(f, "{}", frame.name())?;
+            for (j, location) in frame.locations.iter().enumerate() {
+                if j > 0 {
+                    write!(
+                        f,
+                        "\n        inlined into {}",
+                        location.function.as_deref().unwrap_or("?")
+                    )?;
+                }
+                if let Some(file) = &location.file {
+                    write!(f, "\n          at {file}")?;
+                    if let Some(line) = location.line {
+                        write!(f, ":{line}")?;
+                        if let Some(column) = location.column {
+                            write!(f, ":{column}")?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rust のシンボル名ならデマングルし、ハッシュを落とす。
+fn demangle(name: &str) -> String {
+    match rustc_demangle::try_demangle(name) {
+        Ok(demangled) => format!("{demangled:#}"),
+        Err(_) => name.to_string(),
+    }
+}
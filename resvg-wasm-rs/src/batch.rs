@@ -0,0 +1,223 @@
+//! 1 つのコンパイル済みモジュールで多数の SVG をまとめてレンダリングする。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{EncodeOptions, ImageFormat, RenderError, RenderOptions, Renderer};
+
+/// バッチの 1 件分の入出力パス。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchItem {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// 1 件分のレンダリング結果。
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub item: BatchItem,
+    pub result: Result<RenderedFile, RenderError>,
+}
+
+/// 書き出した画像の情報。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderedFile {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// ファイルのサイズ (バイト)
+    pub size: usize,
+}
+
+/// バッチ全体の結果。`outcomes` は入力と同じ順に並ぶ。
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub outcomes: Vec<BatchOutcome>,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.succeeded()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &BatchOutcome> {
+        self.outcomes.iter().filter(|o| o.result.is_err())
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rendered, {} failed, {} total",
+            self.succeeded(),
+            self.failed(),
+            self.outcomes.len()
+        )
+    }
+}
+
+/// ディレクトリまたは glob パターンから入力 SVG を集め、出力パスを割り当てる。
+///
+/// - ディレクトリは再帰的に `*.svg` を探し、`out_dir` の下に同じ構成で出力する
+/// - それ以外は glob パターンとして展開し、パターンのワイルドカードより前の部分からの
+///   相対パスで `out_dir` の下に出力する (`in/*/icon.svg` なら `out_dir/a/icon.png` など)
+///
+/// 出力ファイルの拡張子は `format` に合わせる。
+/// 結果は入力パスの順に並べ、同じ入力が複数回現れた場合は 1 件にまとめる。
+/// 異なる入力の出力パスが重なる場合は、上書きしないようにエラーを返す。
+pub fn plan(
+    inputs: &[String],
+    out_dir: &Path,
+    format: ImageFormat,
+) -> Result<Vec<BatchItem>, RenderError> {
+    let ext = format.extension();
+    let items: Vec<BatchItem> = collect(inputs)?
+        .into_iter()
+        .map(|(input, relative)| BatchItem {
+            output: out_dir.join(relative).with_extension(ext),
+            input,
+        })
+        .collect();
+
+    let mut outputs: HashMap<&Path, &Path> = HashMap::new();
+    for item in &items {
+        if let Some(other) = outputs.insert(&item.output, &item.input) {
+            return Err(RenderError::InvalidOptions(format!(
+                "{} and {} would both be written to {}",
+                other.display(),
+                item.input.display(),
+                item.output.display()
+            )));
+        }
+    }
+    Ok(items)
+}
+
+/// [`plan`] と同じ規則で入力 SVG だけを集める (出力先を持たない `bench` 用)。
+pub fn find_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, RenderError> {
+    Ok(collect(inputs)?
+        .into_iter()
+        .map(|(input, _)| input)
+        .collect())
+}
+
+/// 入力 SVG のパスと、出力先のディレクトリからの相対パスの組を集める。
+fn collect(inputs: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, RenderError> {
+    let mut found = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut files = Vec::new();
+            collect_svgs(path, &mut files)?;
+            for file in files {
+                let relative = file.strip_prefix(path).unwrap_or(&file).to_path_buf();
+                found.push((file, relative));
+            }
+        } else {
+            let paths = glob::glob(input).map_err(|e| {
+                RenderError::InvalidOptions(format!("invalid pattern {input:?}: {e}"))
+            })?;
+            let root = glob_root(input);
+            for file in paths {
+                let file = file.map_err(|e| RenderError::Io(e.into()))?;
+                if !file.is_file() {
+                    continue;
+                }
+                // ワイルドカードを含まないパターンはファイル名だけを使う
+                let relative = file
+                    .strip_prefix(&root)
+                    .ok()
+                    .filter(|relative| !relative.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new(file.file_name().unwrap_or_default()))
+                    .to_path_buf();
+                found.push((file, relative));
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found.dedup_by(|a, b| a.0 == b.0);
+    Ok(found)
+}
+
+/// glob パターンのうち、ワイルドカードを含む最初の要素より前の部分。
+fn glob_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+/// `dir` の下の `*.svg` を再帰的に集める。
+///
+/// ディレクトリへのシンボリックリンクはたどらない (ループしても止まるように)。
+fn collect_svgs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), RenderError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_svgs(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `items` を順にレンダリングして `encode` の形式で書き出す。
+///
+/// 失敗した入力があっても残りのレンダリングは続け、結果は [`BatchReport`] にまとめる。
+/// `on_done` は 1 件終わるごとに呼ばれる。
+pub fn render_batch(
+    renderer: &mut Renderer,
+    items: &[BatchItem],
+    opts: &RenderOptions,
+    encode: &EncodeOptions,
+    mut on_done: impl FnMut(&BatchOutcome),
+) -> BatchReport {
+    let mut report = BatchReport::default();
+    for item in items {
+        let outcome = BatchOutcome {
+            item: item.clone(),
+            result: render_one(renderer, item, opts, encode),
+        };
+        on_done(&outcome);
+        report.outcomes.push(outcome);
+    }
+    report
+}
+
+/// 1 件分の SVG を読み込み、レンダリングして書き出す。
+pub(crate) fn render_one(
+    renderer: &mut Renderer,
+    item: &BatchItem,
+    opts: &RenderOptions,
+    encode: &EncodeOptions,
+) -> Result<RenderedFile, RenderError> {
+    let svg = std::fs::read(&item.input)?;
+    let image = renderer.render_image(&svg, opts, encode)?;
+    if let Some(parent) = item.output.parent() {
+        std::fs::create_dir_all(parent).map_err(RenderError::write(parent))?;
+    }
+    std::fs::write(&item.output, &image.data).map_err(RenderError::write(&item.output))?;
+    Ok(RenderedFile {
+        format: image.format,
+        width: image.width,
+        height: image.height,
+        size: image.data.len(),
+    })
+}
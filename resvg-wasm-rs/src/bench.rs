@@ -0,0 +1,265 @@
+//! コンパイル・インスタンス化・レンダリングの各段階の時間の計測。
+//!
+//! レンダリングは SVG のアップロード、`context_render` の呼び出し、出力のコピーに分けて計る
+//! (内訳は [`Renderer::last_timings`] で取れる)。
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use wasmtime::Module;
+
+use crate::{RenderError, RenderOptions, Renderer, RendererConfig};
+
+/// 1 回のレンダリングで、ゲストとのやり取りにかかった時間の内訳。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderTimings {
+    /// SVG をゲストのメモリに書き込む (`__wbindgen_malloc` を含む)。
+    /// パース済みのコンテキストを使ったときは 0
+    pub upload: Duration,
+    /// `context_render` の呼び出し
+    pub call: Duration,
+    /// 出力をゲストのメモリからコピーし、ゲスト側のバッファを解放する
+    pub copy: Duration,
+}
+
+/// 計測に使う SVG。
+#[derive(Debug, Clone)]
+pub struct BenchInput {
+    pub path: PathBuf,
+    pub svg: Vec<u8>,
+}
+
+impl BenchInput {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, RenderError> {
+        let path = path.into();
+        let svg = std::fs::read(&path)?;
+        Ok(BenchInput { path, svg })
+    }
+}
+
+/// 計測の回数。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchOptions {
+    /// 1 つの SVG をレンダリングする回数 (インスタンス化の計測回数も同じ)
+    pub iterations: u32,
+    /// モジュールをコンパイルする回数
+    pub compile_iterations: u32,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            iterations: 20,
+            compile_iterations: 1,
+        }
+    }
+}
+
+/// 計測値の分布。パーセンタイルは nearest-rank 法で求める。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl Summary {
+    pub fn of(samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let percentile = |p: usize| match sorted.len() {
+            0 => Duration::ZERO,
+            n => sorted[(n * p).div_ceil(100).max(1) - 1],
+        };
+        Summary {
+            count: sorted.len(),
+            p50: percentile(50),
+            p95: percentile(95),
+            max: sorted.last().copied().unwrap_or_default(),
+            total: sorted.iter().sum(),
+        }
+    }
+
+    /// ミリ秒単位の JSON。
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "count": self.count,
+            "p50_ms": millis(self.p50),
+            "p95_ms": millis(self.p95),
+            "max_ms": millis(self.max),
+            "total_ms": millis(self.total),
+        })
+    }
+}
+
+/// 1 つの SVG の計測結果。
+#[derive(Debug, Clone)]
+pub struct InputBench {
+    pub path: PathBuf,
+    /// SVG のサイズ (バイト)
+    pub size: usize,
+    pub upload: Summary,
+    pub call: Summary,
+    pub copy: Summary,
+    /// [`Renderer::render`] 全体 (PNG の検証など、ホスト側の処理を含む)
+    pub total: Summary,
+}
+
+impl InputBench {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "path": self.path.display().to_string(),
+            "size": self.size,
+            "upload": self.upload.to_json(),
+            "call": self.call.to_json(),
+            "copy": self.copy.to_json(),
+            "total": self.total.to_json(),
+        })
+    }
+}
+
+/// 1 つの設定での計測結果。
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    /// モジュールのコンパイル (キャッシュは使わない)
+    pub compile: Summary,
+    /// インスタンス化 (フォントの登録を含む)
+    pub instantiate: Summary,
+    /// 入力と同じ順に並ぶ。失敗した入力は含まない
+    pub inputs: Vec<InputBench>,
+    /// レンダリングに失敗した入力
+    pub failures: Vec<(PathBuf, RenderError)>,
+}
+
+impl BenchReport {
+    /// 計測したレンダリングの回数 (ウォームアップを除く)。
+    pub fn renders(&self) -> usize {
+        self.inputs.iter().map(|input| input.total.count).sum()
+    }
+
+    /// 1 秒あたりのレンダリング回数。
+    pub fn throughput(&self) -> f64 {
+        let secs: f64 = self
+            .inputs
+            .iter()
+            .map(|input| input.total.total.as_secs_f64())
+            .sum();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.renders() as f64 / secs
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "compile": self.compile.to_json(),
+            "instantiate": self.instantiate.to_json(),
+            "renders": self.renders(),
+            "renders_per_second": self.throughput(),
+            "inputs": self.inputs.iter().map(InputBench::to_json).collect::<Vec<_>>(),
+            "failures": self
+                .failures
+                .iter()
+                .map(|(path, error)| json!({
+                    "path": path.display().to_string(),
+                    "error": error.to_string(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// `config` の設定で `wasm` をコンパイルし、各段階の時間を計る。
+///
+/// 各入力は計測の前に 1 回ずつレンダリングしておく (遅延初期化やメモリの拡張を計測から外す)。
+/// レンダリングに失敗した入力は [`BenchReport::failures`] に入れて計測を続ける。
+/// `config.cache` は使わない。
+pub fn run(
+    wasm: &[u8],
+    config: &RendererConfig,
+    inputs: &[BenchInput],
+    opts: &RenderOptions,
+    bench: &BenchOptions,
+) -> Result<BenchReport, RenderError> {
+    let engine = config.engine()?;
+    if engine.detect_precompiled(wasm).is_some() {
+        return Err(RenderError::InvalidOptions(
+            "bench compiles the module itself and needs a .wasm, not a precompiled .cwasm"
+                .to_string(),
+        ));
+    }
+    let mut module = None;
+    let compile = measure(bench.compile_iterations.max(1), || {
+        module = Some(Module::new(&engine, wasm)?);
+        Ok(())
+    })?;
+    let module = module.expect("compiled at least once");
+
+    let mut renderer = Renderer::with_config(engine, module, config)?;
+    // fork のインスタンスと合わせて、プーリングの上限を超えないように
+    renderer.release_guest();
+    let instantiate = measure(bench.iterations, || renderer.fork().map(drop))?;
+
+    let mut report = BenchReport {
+        compile,
+        instantiate,
+        ..BenchReport::default()
+    };
+    for input in inputs {
+        match bench_input(&mut renderer, input, opts, bench.iterations) {
+            Ok(result) => report.inputs.push(result),
+            Err(error) => report.failures.push((input.path.clone(), error)),
+        }
+    }
+    Ok(report)
+}
+
+fn bench_input(
+    renderer: &mut Renderer,
+    input: &BenchInput,
+    opts: &RenderOptions,
+    iterations: u32,
+) -> Result<InputBench, RenderError> {
+    renderer.render(&input.svg, opts)?;
+
+    let mut upload = Vec::new();
+    let mut call = Vec::new();
+    let mut copy = Vec::new();
+    let total = measure(iterations, || {
+        renderer.render(&input.svg, opts)?;
+        let timings = renderer.last_timings();
+        upload.push(timings.upload);
+        call.push(timings.call);
+        copy.push(timings.copy);
+        Ok(())
+    })?;
+    Ok(InputBench {
+        path: input.path.clone(),
+        size: input.svg.len(),
+        upload: Summary::of(&upload),
+        call: Summary::of(&call),
+        copy: Summary::of(&copy),
+        total,
+    })
+}
+
+/// `f` を `iterations` 回実行し、1 回ごとの時間をまとめる。
+fn measure(
+    iterations: u32,
+    mut f: impl FnMut() -> Result<(), RenderError>,
+) -> Result<Summary, RenderError> {
+    let mut samples = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        f()?;
+        samples.push(started.elapsed());
+    }
+    Ok(Summary::of(&samples))
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
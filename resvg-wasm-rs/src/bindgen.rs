@@ -0,0 +1,586 @@
+//! wasm-bindgen が生成するインポートのホスト側実装。
+//!
+//! resvg_wasm.wasm は JS から使う前提でビルドされているため、wasm-bindgen の組み込み関数
+//! (`__wbindgen_*`) や JS のグルー関数 (`__wbg_*`) をインポートしている。
+//! [`link`] は `module.imports()` を見て、知っている組み込み関数はここで実装したものを、
+//! それ以外には呼ばれた時点でエラーを返すスタブを `Linker` に登録する。
+//!
+//! externref を使う wasm-bindgen は、ゲストがエクスポートする `__wbindgen_export_*` の
+//! externref テーブルを JS グルーに伸ばしてもらう。ホストは JS の値を externref では渡さず
+//! [`JsHeap`] のハンドルで扱うので、テーブルの要素はすべて null にする。
+
+use std::collections::HashSet;
+use std::fmt;
+
+use wasmtime::{
+    Caller, Extern, FuncType, Linker, Memory, Module, Ref, RefType, Result, Table, Val, ValType,
+};
+
+use crate::error::GuestThrow;
+use crate::guest;
+use crate::limits::LimitExceeded;
+use crate::HostState;
+
+/// wasm-bindgen がインポートに使うモジュール名。
+const BINDGEN_MODULES: &[&str] = &[
+    "__wbindgen_placeholder__",
+    "wbg",
+    "__wbindgen_externref_xform__",
+    "__wbindgen_anyref_xform__",
+];
+
+/// `__wbindgen_throw` などで投げられたエラーメッセージは、この長さ (バイト) で切り詰める
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// externref テーブルのエクスポート名の接頭辞
+const EXTERNREF_TABLE_PREFIX: &str = "__wbindgen_export_";
+
+/// JS 側のヒープの先頭 128 個はスタック用に予約されている
+const HEAP_RESERVED: usize = 128;
+
+/// ホスト側で表現する JS の値。
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JsValue {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Error(String),
+    /// `__wbindgen_memory` が返す `WebAssembly.Memory`
+    Memory,
+}
+
+impl fmt::Display for JsValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsValue::Undefined => f.write_str("undefined"),
+            JsValue::Null => f.write_str("null"),
+            JsValue::Bool(b) => write!(f, "{b}"),
+            JsValue::Number(n) => write!(f, "{n}"),
+            JsValue::String(s) => write!(f, "{s:?}"),
+            JsValue::Error(message) => write!(f, "Error: {message}"),
+            JsValue::Memory => f.write_str("[object WebAssembly.Memory]"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Slot {
+    Used(JsValue),
+    /// 空きスロット。次の空きスロットの位置を持つ
+    Free(usize),
+}
+
+/// wasm-bindgen の JS グルーが持つ `heap` 配列と同じ形のスラブ。
+///
+/// ゲストは JS の値を i32 のハンドルで参照する。先頭 128 個は予約で、
+/// その直後に `undefined`, `null`, `true`, `false` が固定で置かれる。
+#[derive(Debug)]
+pub(crate) struct JsHeap {
+    slots: Vec<Slot>,
+    next_free: usize,
+}
+
+impl Default for JsHeap {
+    fn default() -> Self {
+        let mut slots: Vec<Slot> = (0..HEAP_RESERVED)
+            .map(|_| Slot::Used(JsValue::Undefined))
+            .collect();
+        slots.extend(
+            [
+                JsValue::Undefined,
+                JsValue::Null,
+                JsValue::Bool(true),
+                JsValue::Bool(false),
+            ]
+            .map(Slot::Used),
+        );
+        let next_free = slots.len();
+        JsHeap { slots, next_free }
+    }
+}
+
+impl JsHeap {
+    /// 固定の値を除いた最初のハンドル
+    const FIRST_DYNAMIC: usize = HEAP_RESERVED + 4;
+
+    pub(crate) fn insert(&mut self, value: JsValue) -> i32 {
+        let index = self.next_free;
+        if index == self.slots.len() {
+            self.slots.push(Slot::Used(value));
+            self.next_free = self.slots.len();
+        } else {
+            let Slot::Free(next) = std::mem::replace(&mut self.slots[index], Slot::Used(value))
+            else {
+                unreachable!("free list points at a used slot");
+            };
+            self.next_free = next;
+        }
+        index as i32
+    }
+
+    pub(crate) fn get(&self, handle: i32) -> Option<&JsValue> {
+        match self.slots.get(handle as u32 as usize)? {
+            Slot::Used(value) => Some(value),
+            Slot::Free(_) => None,
+        }
+    }
+
+    pub(crate) fn remove(&mut self, handle: i32) {
+        let index = handle as u32 as usize;
+        if index < Self::FIRST_DYNAMIC || !matches!(self.slots.get(index), Some(Slot::Used(_))) {
+            return;
+        }
+        self.slots[index] = Slot::Free(self.next_free);
+        self.next_free = index;
+    }
+}
+
+/// 組み込み関数のシグネチャに現れる型。
+#[derive(Debug, Clone, Copy)]
+enum Ty {
+    I32,
+    F64,
+}
+
+impl Ty {
+    fn matches(self, ty: &ValType) -> bool {
+        match self {
+            Ty::I32 => matches!(ty, ValType::I32),
+            Ty::F64 => matches!(ty, ValType::F64),
+        }
+    }
+}
+
+type Impl = fn(&mut Caller<'_, HostState>, &[Val], &mut [Val]) -> Result<()>;
+
+/// ゲストの externref テーブルを受け取る組み込み関数の実装。
+type TableImpl = fn(&mut Caller<'_, HostState>, Table, &[Val], &mut [Val]) -> Result<()>;
+
+#[derive(Clone, Copy)]
+enum Call {
+    Plain(Impl),
+    /// externref テーブルをエクスポートしているゲストでしか実装できない
+    Table(TableImpl),
+}
+
+/// ホスト側で実装する wasm-bindgen の組み込み関数。
+struct Intrinsic {
+    name: &'static str,
+    params: &'static [Ty],
+    results: &'static [Ty],
+    call: Call,
+}
+
+impl Intrinsic {
+    fn matches(&self, ty: &FuncType) -> bool {
+        ty.params().len() == self.params.len()
+            && ty.results().len() == self.results.len()
+            && ty.params().zip(self.params).all(|(ty, p)| p.matches(&ty))
+            && ty.results().zip(self.results).all(|(ty, r)| r.matches(&ty))
+    }
+}
+
+use Ty::{F64, I32};
+
+const INTRINSICS: &[Intrinsic] = &[
+    Intrinsic {
+        name: "__wbindgen_throw",
+        params: &[I32, I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            let message = read_string(caller, params[0].unwrap_i32(), params[1].unwrap_i32())?;
+            Err(throw(message))
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_rethrow",
+        params: &[I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            let message = match caller.data().heap.get(params[0].unwrap_i32()) {
+                Some(JsValue::Error(message)) => message.clone(),
+                Some(value) => value.to_string(),
+                None => "invalid handle".to_string(),
+            };
+            Err(throw(message))
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_string_new",
+        params: &[I32, I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let s = read_string(caller, params[0].unwrap_i32(), params[1].unwrap_i32())?;
+            results[0] = Val::I32(caller.data_mut().heap.insert(JsValue::String(s)));
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_error_new",
+        params: &[I32, I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let s = read_string(caller, params[0].unwrap_i32(), params[1].unwrap_i32())?;
+            results[0] = Val::I32(caller.data_mut().heap.insert(JsValue::Error(s)));
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_number_new",
+        params: &[F64],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let n = params[0].unwrap_f64();
+            results[0] = Val::I32(caller.data_mut().heap.insert(JsValue::Number(n)));
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_memory",
+        params: &[],
+        results: &[I32],
+        call: Call::Plain(|caller, _, results| {
+            results[0] = Val::I32(caller.data_mut().heap.insert(JsValue::Memory));
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_object_clone_ref",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let heap = &mut caller.data_mut().heap;
+            let value = heap.get(params[0].unwrap_i32()).cloned();
+            results[0] = Val::I32(heap.insert(value.unwrap_or(JsValue::Undefined)));
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_object_drop_ref",
+        params: &[I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            caller.data_mut().heap.remove(params[0].unwrap_i32());
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_cb_drop",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            caller.data_mut().heap.remove(params[0].unwrap_i32());
+            results[0] = Val::I32(1);
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_is_undefined",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let value = caller.data().heap.get(params[0].unwrap_i32());
+            results[0] = Val::I32(matches!(value, Some(JsValue::Undefined)) as i32);
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_is_null",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let value = caller.data().heap.get(params[0].unwrap_i32());
+            results[0] = Val::I32(matches!(value, Some(JsValue::Null)) as i32);
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_is_string",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            let value = caller.data().heap.get(params[0].unwrap_i32());
+            results[0] = Val::I32(matches!(value, Some(JsValue::String(_))) as i32);
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_boolean_get",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Plain(|caller, params, results| {
+            // JS グルーと同じく、真偽値でなければ 2 を返す
+            results[0] = Val::I32(match caller.data().heap.get(params[0].unwrap_i32()) {
+                Some(JsValue::Bool(b)) => *b as i32,
+                _ => 2,
+            });
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_number_get",
+        params: &[I32, I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            // retptr に [is_some: i32, pad: i32, value: f64] を書き込む
+            let number = match caller.data().heap.get(params[1].unwrap_i32()) {
+                Some(JsValue::Number(n)) => Some(*n),
+                _ => None,
+            };
+            let mut out = [0u8; 16];
+            out[0..4].copy_from_slice(&(number.is_some() as i32).to_le_bytes());
+            out[8..16].copy_from_slice(&number.unwrap_or(0.0).to_le_bytes());
+            write(caller, params[0].unwrap_i32(), &out)
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_string_get",
+        params: &[I32, I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            let s = match caller.data().heap.get(params[1].unwrap_i32()) {
+                Some(JsValue::String(s)) => Some(s.clone()),
+                _ => None,
+            };
+            write_string_ret(caller, params[0].unwrap_i32(), s.as_deref())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_debug_string",
+        params: &[I32, I32],
+        results: &[],
+        call: Call::Plain(|caller, params, _| {
+            let s = match caller.data().heap.get(params[1].unwrap_i32()) {
+                Some(value) => value.to_string(),
+                None => "undefined".to_string(),
+            };
+            write_string_ret(caller, params[0].unwrap_i32(), Some(&s))
+        }),
+    },
+    // wasm-bindgen の CLI がビルド時に解釈するための関数で、実行時には呼ばれない
+    Intrinsic {
+        name: "__wbindgen_describe",
+        params: &[I32],
+        results: &[],
+        call: Call::Plain(|_, _, _| Ok(())),
+    },
+    Intrinsic {
+        name: "__wbindgen_describe_closure",
+        params: &[I32, I32, I32],
+        results: &[I32],
+        call: Call::Plain(|_, _, results| {
+            results[0] = Val::I32(0);
+            Ok(())
+        }),
+    },
+    // JS グルーと同じく、0 番と、末尾に足した undefined, null, true, false の 4 つを埋める
+    Intrinsic {
+        name: "__wbindgen_init_externref_table",
+        params: &[],
+        results: &[],
+        call: Call::Table(|caller, table, _, _| {
+            let offset = table.grow(&mut *caller, 4, Ref::Extern(None))?;
+            table.set(&mut *caller, 0, Ref::Extern(None))?;
+            for index in offset..offset + 4 {
+                table.set(&mut *caller, index, Ref::Extern(None))?;
+            }
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_externref_table_grow",
+        params: &[I32],
+        results: &[I32],
+        call: Call::Table(|caller, table, params, results| {
+            // wasm の table.grow と同じく、伸ばせなければ -1 を返す。
+            // ResourceLimits の上限を超えたときは、ゲストの table.grow と同じくトラップにする
+            let delta = params[0].unwrap_i32() as u32 as u64;
+            results[0] = Val::I32(match table.grow(&mut *caller, delta, Ref::Extern(None)) {
+                Ok(old) => old as i32,
+                Err(error) if error.is::<LimitExceeded>() => return Err(error),
+                Err(_) => -1,
+            });
+            Ok(())
+        }),
+    },
+    Intrinsic {
+        name: "__wbindgen_externref_table_set_null",
+        params: &[I32],
+        results: &[],
+        call: Call::Table(|caller, table, params, _| {
+            let index = params[0].unwrap_i32() as u32 as u64;
+            table.set(&mut *caller, index, Ref::Extern(None))
+        }),
+    },
+];
+
+/// `module` がインポートする wasm-bindgen の関数をすべて `linker` に登録する。
+///
+/// 既知の組み込み関数は型が一致すればホスト側の実装を、そうでなければ
+/// 呼ばれた時点でエラーを返すスタブを登録する。wasm-bindgen 以外のモジュールからの
+/// インポートには触れないので、足りなければインスタンス化の時点で失敗する。
+pub(crate) fn link(linker: &mut Linker<HostState>, module: &Module) -> Result<()> {
+    let table = externref_table(module);
+    let mut registered = HashSet::new();
+    for import in module.imports() {
+        let (module_name, name) = (import.module(), import.name());
+        if !BINDGEN_MODULES.contains(&module_name) {
+            continue;
+        }
+        let Some(ty) = import.ty().func().cloned() else {
+            continue;
+        };
+        // 同じ関数を複数回インポートしていても、登録は 1 回だけ
+        if !registered.insert((module_name, name)) {
+            continue;
+        }
+
+        let intrinsic = INTRINSICS
+            .iter()
+            .find(|i| i.name == name && i.matches(&ty))
+            .map(|i| i.call);
+        match (intrinsic, table) {
+            (Some(Call::Plain(call)), _) => {
+                linker.func_new(module_name, name, ty, move |mut caller, params, results| {
+                    call(&mut caller, params, results)
+                })?;
+            }
+            (Some(Call::Table(call)), Some(table)) => {
+                let table = table.to_string();
+                linker.func_new(module_name, name, ty, move |mut caller, params, results| {
+                    let Some(Extern::Table(table)) = caller.get_export(&table) else {
+                        anyhow::bail!("the guest has no table export `{table}`");
+                    };
+                    call(&mut caller, table, params, results)
+                })?;
+            }
+            _ => {
+                let qualified = format!("{module_name}::{name}");
+                linker.func_new(module_name, name, ty, move |_, _, _| {
+                    anyhow::bail!("unsupported wasm-bindgen import {qualified} was called")
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `module_name::name` のインポートを [`link`] がどう登録するか。
+///
+/// wasm-bindgen のモジュールでなければ `None`。ホスト側の実装があれば `Some(true)`、
+/// 呼ばれた時点でエラーを返すスタブになるなら `Some(false)`。externref テーブルを操作する
+/// 関数は、`module` がテーブルをエクスポートしていなければスタブになる。
+pub(crate) fn provides(
+    module: &Module,
+    module_name: &str,
+    name: &str,
+    ty: &FuncType,
+) -> Option<bool> {
+    if !BINDGEN_MODULES.contains(&module_name) {
+        return None;
+    }
+    Some(
+        INTRINSICS
+            .iter()
+            .find(|i| i.name == name && i.matches(ty))
+            .is_some_and(|i| match i.call {
+                Call::Plain(_) => true,
+                Call::Table(_) => externref_table(module).is_some(),
+            }),
+    )
+}
+
+/// `module` がエクスポートする wasm-bindgen の externref テーブルの名前。
+fn externref_table(module: &Module) -> Option<&str> {
+    module
+        .exports()
+        .find(|export| {
+            export.name().starts_with(EXTERNREF_TABLE_PREFIX)
+                && export
+                    .ty()
+                    .table()
+                    .is_some_and(|table| table.element().matches(&RefType::EXTERNREF))
+        })
+        .map(|export| export.name())
+}
+
+pub(crate) fn memory(caller: &mut Caller<'_, HostState>) -> Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => anyhow::bail!("wasm-bindgen import was called but the guest has no memory export"),
+    }
+}
+
+/// ゲストのメモリから `ptr..ptr+len` の UTF-8 文字列を読み出す。
+/// ゲストのメモリから `len` バイトの文字列を読む。範囲外ならトラップにする。
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String> {
+    let memory = memory(caller)?;
+    let data = memory.data(&*caller);
+    let start = ptr as u32 as usize;
+    let len = len as u32 as usize;
+    let Some(bytes) = data.get(start..start.saturating_add(len)) else {
+        anyhow::bail!(
+            "string out of bounds: ptr={start} len={len} memory={}",
+            data.len()
+        );
+    };
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// ゲストが投げたエラー。メッセージは [`MAX_MESSAGE`] バイトまでに切り詰める。
+fn throw(mut message: String) -> anyhow::Error {
+    if message.len() > MAX_MESSAGE {
+        let mut end = MAX_MESSAGE;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+        message.push_str("...");
+    }
+    GuestThrow(message).into()
+}
+
+fn write(caller: &mut Caller<'_, HostState>, ptr: i32, bytes: &[u8]) -> Result<()> {
+    let memory = memory(caller)?;
+    memory.write(&mut *caller, ptr as u32 as usize, bytes)?;
+    Ok(())
+}
+
+/// 文字列をゲストのアロケータで確保した領域にコピーし、retptr に `[ptr, len]` を書き込む。
+/// `None` のときは `[0, 0]` (Rust 側では `None`) を書き込む。
+fn write_string_ret(
+    caller: &mut Caller<'_, HostState>,
+    retptr: i32,
+    s: Option<&str>,
+) -> Result<()> {
+    write_bytes_ret(caller, retptr, s.map(str::as_bytes))
+}
+
+/// [`write_string_ret`] のバイト列版。
+pub(crate) fn write_bytes_ret(
+    caller: &mut Caller<'_, HostState>,
+    retptr: i32,
+    s: Option<&[u8]>,
+) -> Result<()> {
+    let (ptr, len) = match s {
+        Some(s) => {
+            let malloc = caller
+                .get_export("__wbindgen_malloc")
+                .and_then(Extern::into_func);
+            let realloc = caller
+                .get_export("__wbindgen_realloc")
+                .and_then(Extern::into_func);
+            let ptr = guest::alloc_with(&mut *caller, malloc, realloc, s.len())?;
+            write(caller, ptr, s)?;
+            (ptr, s.len() as i32)
+        }
+        None => (0, 0),
+    };
+
+    let mut out = [0u8; 8];
+    out[0..4].copy_from_slice(&ptr.to_le_bytes());
+    out[4..8].copy_from_slice(&len.to_le_bytes());
+    write(caller, retptr, &out)
+}
@@ -0,0 +1,159 @@
+//! resvg モジュールの AOT コンパイル結果 (`.cwasm`) の保存と読み込み。
+
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tracing::field::Empty;
+use tracing::{debug, info_span, warn};
+use wasmtime::{Engine, Module, Precompiled};
+
+use crate::RenderError;
+
+/// コンパイル済みモジュールのキャッシュディレクトリ。
+///
+/// キャッシュのキーは wasm の内容のハッシュと `Engine::precompile_compatibility_hash`
+/// (wasmtime のバージョン・ターゲット・コンパイル設定を含む) から作るので、
+/// wasm を差し替えたり wasmtime を更新したりすると自動的に別のエントリになる。
+#[derive(Debug, Clone)]
+pub struct ModuleCache {
+    dir: PathBuf,
+}
+
+impl ModuleCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ModuleCache { dir: dir.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/resvg-wasm` または `$HOME/.cache/resvg-wasm`。
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("resvg-wasm"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// `wasm` を `engine` でコンパイルした結果を置くパス。
+    pub fn path_for(&self, engine: &Engine, wasm: &[u8]) -> PathBuf {
+        let mut hasher = Sha256Hasher(Sha256::new());
+        hasher.0.update(Sha256::digest(wasm));
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let key = hasher.0.finalize();
+
+        let name: String = key.iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("{name}.cwasm"))
+    }
+
+    /// キャッシュにあればそれを読み込み、なければコンパイルして保存する。
+    ///
+    /// キャッシュの読み込みや書き込みに失敗しても、コンパイルできればエラーにはしない。
+    pub fn load(&self, engine: &Engine, wasm: &[u8]) -> Result<Module, RenderError> {
+        let path = self.path_for(engine, wasm);
+        if path.is_file() {
+            // SAFETY: キャッシュディレクトリには自分で precompile_module した結果しか置かない
+            match unsafe { Module::deserialize_file(engine, &path) } {
+                Ok(module) => {
+                    debug!(path = %path.display(), "loaded the module from the cache");
+                    return Ok(module);
+                }
+                Err(error) => {
+                    warn!(path = %path.display(), %error, "ignoring a broken cache entry")
+                }
+            }
+        }
+
+        let serialized = engine.precompile_module(wasm)?;
+        // SAFETY: 直前に同じ Engine で precompile_module した結果
+        let module = unsafe { Module::deserialize(engine, &serialized)? };
+        if let Err(error) = write_atomic(&path, &serialized) {
+            warn!(path = %path.display(), %error, "failed to write the module cache");
+        }
+        Ok(module)
+    }
+}
+
+/// `path` のモジュールを読み込む。
+///
+/// `path` が `precompile` で作った `.cwasm` ならそのまま読み込み、
+/// wasm ならコンパイルする (`cache` があればそれを使う)。
+pub fn load_module(
+    engine: &Engine,
+    path: &Path,
+    cache: Option<&ModuleCache>,
+) -> Result<Module, RenderError> {
+    let load_error = |error: wasmtime::Error| RenderError::Load {
+        path: path.to_path_buf(),
+        error,
+    };
+    let span =
+        info_span!("load_module", path = %path.display(), size = Empty, source = Empty).entered();
+    let bytes = std::fs::read(path).map_err(|e| load_error(e.into()))?;
+    span.record("size", bytes.len());
+    let precompiled = engine.detect_precompiled(&bytes);
+    span.record(
+        "source",
+        match (precompiled, cache) {
+            (Some(_), _) => "precompiled",
+            (None, Some(_)) => "cache",
+            (None, None) => "compile",
+        },
+    );
+    match precompiled {
+        Some(Precompiled::Module) => {
+            // SAFETY: .cwasm は信頼できるものだけを渡す前提 (precompile サブコマンドの出力)
+            unsafe { Module::deserialize(engine, &bytes) }.map_err(load_error)
+        }
+        Some(Precompiled::Component) => Err(load_error(anyhow::anyhow!(
+            "it is a precompiled component, not a core module"
+        ))),
+        None => match cache {
+            Some(cache) => cache.load(engine, &bytes).map_err(|e| match e {
+                RenderError::Wasm(error) => load_error(error),
+                e => e,
+            }),
+            None => Module::new(engine, &bytes).map_err(load_error),
+        },
+    }
+}
+
+/// `wasm` を AOT コンパイルし、`output` に `.cwasm` として書き出す。
+pub fn precompile(engine: &Engine, wasm: &Path, output: &Path) -> Result<(), RenderError> {
+    let load_error = |error: wasmtime::Error| RenderError::Load {
+        path: wasm.to_path_buf(),
+        error,
+    };
+    let bytes = std::fs::read(wasm).map_err(|e| load_error(e.into()))?;
+    let serialized = engine.precompile_module(&bytes).map_err(load_error)?;
+    write_atomic(output, &serialized).map_err(RenderError::write(output))?;
+    Ok(())
+}
+
+/// 一時ファイルに書いてから rename し、途中まで書かれたファイルを残さない。
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+/// `Hash` の出力を SHA-256 に流し込むためのアダプタ。
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        unreachable!("only used as a sink for Hash::hash")
+    }
+}
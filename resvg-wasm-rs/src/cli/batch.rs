@@ -0,0 +1,74 @@
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use clap::Args;
+use resvg_wasm::batch;
+use resvg_wasm::pool::RenderPool;
+
+use super::render::save_coredump;
+use super::{GlobalArgs, RenderFlags};
+
+#[derive(Debug, Args)]
+pub struct BatchCommand {
+    /// 入力ディレクトリ (再帰的に *.svg を探す) または glob パターン。
+    /// 出力はディレクトリ、またはパターンのワイルドカードより前の部分からの相対パスに置く
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+
+    /// 出力ディレクトリ
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// 並列に動かすワーカー数 (省略時は CPU 数)
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+
+    #[command(flatten)]
+    pub flags: RenderFlags,
+}
+
+impl BatchCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let options = self.flags.to_options();
+        options.validate()?;
+        let encode = self.flags.encode_options(None);
+
+        let items = batch::plan(&self.inputs, &self.output, encode.format)?;
+        if items.is_empty() {
+            anyhow::bail!("no SVG files matched {:?}", self.inputs);
+        }
+
+        // モジュールのコンパイルは全件で 1 回だけ
+        let renderer = global.renderer()?;
+        let jobs = self.jobs.unwrap_or_else(RenderPool::default_jobs);
+        let mut pool = RenderPool::new(renderer, jobs);
+        let report = pool.render_batch(&items, &options, &encode, |outcome| {
+            if let Ok(file) = &outcome.result {
+                eprintln!(
+                    "ok    {} -> {} ({}x{})",
+                    outcome.item.input.display(),
+                    outcome.item.output.display(),
+                    file.width,
+                    file.height
+                );
+            }
+        })?;
+
+        for outcome in report.failures() {
+            if let Err(err) = &outcome.result {
+                eprintln!("FAIL  {}: {err}", outcome.item.input.display());
+                save_coredump(err, &outcome.item.input);
+            }
+        }
+        eprintln!("{report}");
+
+        if report.failed() > 0 {
+            anyhow::bail!(
+                "{} of {} files failed",
+                report.failed(),
+                report.outcomes.len()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use clap::Args;
+use resvg_wasm::batch;
+use resvg_wasm::bench::{self, BenchInput, BenchOptions, BenchReport, Summary};
+use resvg_wasm::{EngineOptions, RendererConfig};
+use serde_json::json;
+
+use super::render::save_coredump;
+use super::{GlobalArgs, RenderFlags};
+
+#[derive(Debug, Args)]
+#[command(after_help = "\
+レンダリングは upload (SVG をゲストのメモリへ書き込む)、call (context_render の呼び出し)、
+copy (出力をゲストのメモリから読み出して解放する) に分け、SVG ごとに p50/p95/max を表示する。
+total はホスト側の処理も含めた 1 回のレンダリング全体。
+
+--compare では、グローバルの --opt-level, --pooling, --no-simd などで決まる設定を基準に、
+設定を 1 つずつ変えて同じ計測を繰り返し、基準からのスループットの変化を表示する。
+コンパイル済みモジュールのキャッシュは使わない。")]
+pub struct BenchCommand {
+    /// 入力ディレクトリ (再帰的に *.svg を探す) または glob パターン
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+
+    /// 1 つの SVG をレンダリングする回数 (インスタンス化の計測回数も同じ)
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 20,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// モジュールをコンパイルする回数
+    #[arg(long, value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    pub compile_iterations: u32,
+
+    /// wasmtime の設定を 1 つずつ変えたときの効果を比べる
+    #[arg(long)]
+    pub compare: bool,
+
+    /// 結果を JSON で標準出力に書く
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub flags: RenderFlags,
+}
+
+impl BenchCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let options = self.flags.to_options();
+        options.validate()?;
+
+        let paths = batch::find_inputs(&self.inputs)?;
+        if paths.is_empty() {
+            anyhow::bail!("no SVG files matched {:?}", self.inputs);
+        }
+        let inputs = paths
+            .into_iter()
+            .map(BenchInput::load)
+            .collect::<Result<Vec<_>, _>>()?;
+        let wasm = std::fs::read(&global.wasm)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", global.wasm.display()))?;
+
+        let config = RendererConfig {
+            fonts: global.fonts()?,
+            ..global.config()?
+        };
+        let mut variants = vec![("baseline".to_string(), config.engine.clone())];
+        if self.compare {
+            variants.extend(config.engine.variants());
+        }
+        let bench_options = BenchOptions {
+            iterations: self.iterations,
+            compile_iterations: self.compile_iterations,
+        };
+
+        eprintln!(
+            "{} SVG files x {} iterations",
+            inputs.len(),
+            self.iterations
+        );
+        if self.compare && !self.json {
+            println!(
+                "{:<28} {:>12} {:>12} {:>12}",
+                "engine", "compile", "instantiate", "renders/s"
+            );
+        }
+        let mut results = Vec::new();
+        let mut failed = 0;
+        let mut baseline: Option<f64> = None;
+        for (name, engine) in variants {
+            let config = RendererConfig {
+                engine: engine.clone(),
+                ..config.clone()
+            };
+            let report = bench::run(&wasm, &config, &inputs, &options, &bench_options)
+                .map_err(|e| anyhow::Error::new(e).context(format!("{name} failed")))?;
+            for (path, err) in &report.failures {
+                eprintln!("FAIL  {name}: {}: {err}", path.display());
+                save_coredump(err, path);
+            }
+            failed += report.failures.len();
+
+            if self.json {
+                results.push(json!({
+                    "name": name,
+                    "options": engine_json(&engine),
+                    "report": report.to_json(),
+                }));
+            } else if self.compare {
+                let throughput = report.throughput();
+                let change = match baseline {
+                    Some(base) if base > 0.0 => {
+                        format!("  ({:+.1}%)", (throughput / base - 1.0) * 100.0)
+                    }
+                    _ => String::new(),
+                };
+                baseline.get_or_insert(throughput);
+                println!(
+                    "{name:<28} {:>9.1} ms {:>9.3} ms {:>12.1}{change}",
+                    millis(report.compile.p50),
+                    millis(report.instantiate.p50),
+                    throughput
+                );
+            } else {
+                print_report(&report);
+            }
+        }
+
+        if self.json {
+            let output = json!({
+                "wasm": global.wasm.display().to_string(),
+                "iterations": self.iterations,
+                "compile_iterations": self.compile_iterations,
+                "engines": results,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        if failed > 0 {
+            anyhow::bail!("{failed} renders failed");
+        }
+        Ok(())
+    }
+}
+
+fn print_report(report: &BenchReport) {
+    println!("{}", summary_row("compile", &report.compile));
+    println!("{}", summary_row("instantiate", &report.instantiate));
+    for input in &report.inputs {
+        println!();
+        println!("{} ({} bytes)", input.path.display(), input.size);
+        println!("{}", summary_row("  upload", &input.upload));
+        println!("{}", summary_row("  call", &input.call));
+        println!("{}", summary_row("  copy", &input.copy));
+        println!("{}", summary_row("  total", &input.total));
+    }
+    println!();
+    println!(
+        "{} renders, {:.1} renders/s",
+        report.renders(),
+        report.throughput()
+    );
+}
+
+fn summary_row(label: &str, summary: &Summary) -> String {
+    format!(
+        "{label:<14} p50 {:>9.3} ms   p95 {:>9.3} ms   max {:>9.3} ms",
+        millis(summary.p50),
+        millis(summary.p95),
+        millis(summary.max)
+    )
+}
+
+fn engine_json(engine: &EngineOptions) -> serde_json::Value {
+    json!({
+        "opt_level": engine.opt_level.to_string(),
+        "pooling": engine.pooling,
+        "simd": engine.simd,
+        "relaxed_simd": engine.relaxed_simd,
+        "memory_init_cow": engine.memory_init_cow,
+        "parallel_compilation": engine.parallel_compilation,
+        "debug_info": engine.debug_info,
+        "coredump": engine.coredump,
+    })
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use clap::Args;
+use resvg_wasm::diff::{self, DiffOptions};
+
+use super::render::{read_input, save_coredump, write_output};
+use super::{GlobalArgs, RenderFlags};
+
+#[derive(Debug, Args)]
+#[command(after_help = "\
+不一致のピクセルが --max-diff-pixels を超えるか、画像の大きさが違う場合は終了コード 9 で終わる。")]
+pub struct CompareCommand {
+    /// 入力 SVG のパス (`-` で標準入力)
+    pub input: PathBuf,
+
+    /// 比較する基準 PNG
+    #[arg(short, long, value_name = "PNG")]
+    pub reference: PathBuf,
+
+    /// 1 ピクセルあたりの許容差 (0.0-1.0、チャンネルごとの差の最大値)
+    #[arg(long, default_value_t = 0.1)]
+    pub threshold: f64,
+
+    /// 許容する不一致のピクセル数
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub max_diff_pixels: usize,
+
+    /// 差分画像の出力先 [既定: 基準 PNG の拡張子を .diff.png にしたもの]
+    #[arg(long, value_name = "PNG")]
+    pub diff: Option<PathBuf>,
+
+    /// 比較せず、レンダリング結果で基準 PNG を書き換える
+    #[arg(long)]
+    pub update: bool,
+
+    #[command(flatten)]
+    pub flags: RenderFlags,
+}
+
+impl CompareCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let options = self.flags.to_options();
+        let diff_options = DiffOptions {
+            threshold: self.threshold,
+            max_diff_pixels: self.max_diff_pixels,
+        };
+        options.validate()?;
+        diff_options.validate()?;
+
+        let svg = read_input(&self.input)?;
+        let mut renderer = global.renderer()?;
+        let png = renderer
+            .render(&svg, &options)
+            .inspect_err(|err| save_coredump(err, &self.input))?;
+
+        if self.update {
+            write_output(&self.reference, &png)?;
+            eprintln!("Updated {}", self.reference.display());
+            return Ok(());
+        }
+
+        let reference = std::fs::read(&self.reference)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", self.reference.display()))?;
+        let report = diff::compare(&png, &reference, &diff_options)?;
+
+        if report.reference_size != (report.width, report.height) {
+            let (w, h) = report.reference_size;
+            return Err(Regression(format!(
+                "size mismatch: rendered {}x{}, reference {w}x{h}",
+                report.width, report.height
+            ))
+            .into());
+        }
+
+        eprintln!(
+            "{} of {} pixels differ ({:.3}%), max delta {:.3}",
+            report.mismatched,
+            report.width as usize * report.height as usize,
+            report.mismatch_ratio() * 100.0,
+            report.max_delta
+        );
+        if report.passed() {
+            return Ok(());
+        }
+
+        if let Some(image) = &report.diff_image {
+            let path = self.diff_path();
+            write_output(&path, &image.data)?;
+            eprintln!("Wrote diff image to {}", path.display());
+        }
+        Err(Regression(format!(
+            "{} pixels differ from {} (allowed {})",
+            report.mismatched,
+            self.reference.display(),
+            self.max_diff_pixels
+        ))
+        .into())
+    }
+
+    fn diff_path(&self) -> PathBuf {
+        self.diff
+            .clone()
+            .unwrap_or_else(|| self.reference.with_extension("diff.png"))
+    }
+}
+
+/// レンダリング結果が基準画像と一致しなかった。レンダリング自体の失敗と区別して終了コード 9 にする。
+#[derive(Debug)]
+pub struct Regression(String);
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Regression {}
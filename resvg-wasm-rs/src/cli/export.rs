@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use resvg_wasm::icons::{self, Rendered, Variant};
+use resvg_wasm::{ImageFormat, RenderError};
+
+use super::render::{read_input, save_coredump, write_output};
+use super::{GlobalArgs, RenderFlags};
+
+#[derive(Debug, Args)]
+#[command(after_help = "\
+出力ファイル名のテンプレートで使えるプレースホルダ:
+  {stem} {size} {scale} {width} {height} {ext}
+
+例:
+  resvg-wasm export logo.svg --scales 1,2,3 -o 'out/{stem}@{scale}x.{ext}'
+  resvg-wasm export logo.svg --ico favicon.ico
+  resvg-wasm export logo.svg --iconset Logo.iconset")]
+pub struct ExportCommand {
+    /// 入力 SVG のパス (`-` で標準入力)
+    pub input: PathBuf,
+
+    /// 書き出すサイズ (px, カンマ区切り)。各サイズの正方形に収まるように縮尺する
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "scales"
+    )]
+    pub sizes: Vec<u32>,
+
+    /// 書き出す倍率 (カンマ区切り、例: 1,2,3 または 1x,2x,3x)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = parse_scale)]
+    pub scales: Vec<f64>,
+
+    /// 1 枚ずつ書き出すときのファイル名のテンプレート。--ico / --iconset だけのときは省略できる
+    /// [既定: 入力と同じディレクトリの {stem}-{size}.{ext} または {stem}@{scale}x.{ext}]
+    #[arg(short, long, value_name = "TEMPLATE")]
+    pub output: Option<String>,
+
+    /// PNG をまとめた favicon (.ico) を書き出す
+    #[arg(long, value_name = "FILE")]
+    pub ico: Option<PathBuf>,
+
+    /// Apple の iconset ディレクトリ (icon_16x16.png, icon_16x16@2x.png, ...) を書き出す
+    #[arg(long, value_name = "DIR")]
+    pub iconset: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub flags: RenderFlags,
+}
+
+impl ExportCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let options = self.flags.to_options();
+        let encode = self.flags.encode_options(None);
+        options.validate()?;
+        encode.validate()?;
+
+        let bundles = self.ico.is_some() || self.iconset.is_some();
+        if bundles && encode.format != ImageFormat::Png {
+            anyhow::bail!(
+                "--ico and --iconset need PNG output, got --format {}",
+                encode.format
+            );
+        }
+        let variants = self.variants()?;
+        if let Some(template) = &self.output {
+            // 1 つのファイル名に全部のバリエーションを上書きしてしまわないように
+            if variants.len() > 1 && !template.contains("{size}") && !template.contains("{scale}") {
+                return Err(RenderError::InvalidOptions(format!(
+                    "--output {template:?} needs {{size}} or {{scale}} to write {} variants",
+                    variants.len()
+                ))
+                .into());
+            }
+        }
+
+        let svg = read_input(&self.input)?;
+        let mut renderer = global.renderer()?;
+        let rendered = icons::render_variants(&mut renderer, &svg, &options, &encode, &variants)
+            .inspect_err(|err| save_coredump(err, &self.input))?;
+
+        if let Some(template) = self.template() {
+            let stem = self.stem();
+            for r in &rendered {
+                let path = icons::expand_template(&template, &stem, r)?;
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                write_output(&path, &r.image.data)?;
+                report(r, &path);
+            }
+        }
+
+        if let Some(ico) = &self.ico {
+            let images: Vec<_> = rendered
+                .iter()
+                .map(|r| &r.image)
+                .filter(|image| image.width <= 256 && image.height <= 256)
+                .collect();
+            let data = icons::write_ico(&images)?;
+            write_output(ico, &data)?;
+            eprintln!("Wrote {} images to {}", images.len(), ico.display());
+        }
+
+        if let Some(dir) = &self.iconset {
+            let iconset = icons::write_iconset(dir, &rendered)?;
+            for (variant, reason) in &iconset.skipped {
+                eprintln!("Skipped {variant} for {}: {reason}", dir.display());
+            }
+            eprintln!(
+                "Wrote {} images to {}",
+                iconset.written.len(),
+                dir.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// --sizes / --scales。どちらもなければ --ico / --iconset の既定のサイズ。
+    fn variants(&self) -> anyhow::Result<Vec<Variant>> {
+        if !self.sizes.is_empty() {
+            return Ok(self.sizes.iter().copied().map(Variant::Size).collect());
+        }
+        if !self.scales.is_empty() {
+            return Ok(self.scales.iter().copied().map(Variant::Scale).collect());
+        }
+        let mut sizes = Vec::new();
+        if self.ico.is_some() {
+            sizes.extend_from_slice(icons::ICO_SIZES);
+        }
+        if self.iconset.is_some() {
+            sizes.extend_from_slice(icons::ICONSET_SIZES);
+        }
+        if sizes.is_empty() {
+            anyhow::bail!("one of --sizes, --scales, --ico or --iconset is required");
+        }
+        sizes.sort_unstable();
+        sizes.dedup();
+        Ok(sizes.into_iter().map(Variant::Size).collect())
+    }
+
+    /// 1 枚ずつ書き出すときのテンプレート。--ico / --iconset だけなら書き出さない。
+    fn template(&self) -> Option<String> {
+        if let Some(template) = &self.output {
+            return Some(template.clone());
+        }
+        if self.ico.is_some() || self.iconset.is_some() {
+            return None;
+        }
+        let name = if !self.scales.is_empty() {
+            "{stem}@{scale}x.{ext}"
+        } else {
+            "{stem}-{size}.{ext}"
+        };
+        let dir = self.input.parent().unwrap_or(Path::new(""));
+        Some(dir.join(name).to_string_lossy().into_owned())
+    }
+
+    fn stem(&self) -> String {
+        match self.input.file_stem() {
+            Some(stem) if self.input.as_os_str() != "-" => stem.to_string_lossy().into_owned(),
+            _ => "stdin".to_string(),
+        }
+    }
+}
+
+fn parse_scale(s: &str) -> Result<f64, String> {
+    let scale: f64 = s
+        .trim_end_matches(['x', 'X'])
+        .parse()
+        .map_err(|e| format!("{e}"))?;
+    if scale.is_finite() && scale > 0.0 {
+        Ok(scale)
+    } else {
+        Err(format!("scale must be positive, got {s}"))
+    }
+}
+
+fn report(rendered: &Rendered, path: &Path) {
+    let image = &rendered.image;
+    eprintln!(
+        "{:>8}  {}x{} {} ({} bytes) -> {}",
+        rendered.variant.to_string(),
+        image.width,
+        image.height,
+        image.format,
+        image.data.len(),
+        path.display()
+    );
+}
@@ -0,0 +1,72 @@
+use clap::Args;
+use resvg_wasm::abi::{self, Entry, Status};
+use resvg_wasm::{cache, describe, RenderError};
+
+use super::GlobalArgs;
+
+#[derive(Debug, Args)]
+#[command(after_help = "\
+状態: ok 期待どおり, stub 呼ばれるとエラーになるスタブでつなぐ, unused ホストは使わない,
+absent なくてもよいエクスポートがない, MISSING / MISTYPED / UNRESOLVED 読み込めない。
+問題があれば終了コード 4 で終わる。")]
+pub struct InspectCommand {
+    /// wasm-bindgen の記述子から Rust 側のシグネチャも表示する
+    /// (wasm-bindgen の CLI で処理する前の wasm のみ)
+    #[arg(long)]
+    pub descriptors: bool,
+}
+
+impl InspectCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let engine = global.config()?.engine()?;
+        let module = cache::load_module(&engine, &global.wasm, global.cache().as_ref())?;
+        let inspection = abi::inspect(&module);
+
+        println!("{}", global.wasm.display());
+        print_section("Imports", &inspection.imports);
+        print_section("Exports", &inspection.exports);
+
+        if self.descriptors {
+            let described = describe::read(&module)?;
+            println!();
+            if described.is_empty() {
+                println!("Descriptors: none (already processed by the wasm-bindgen CLI?)");
+            } else {
+                println!("Descriptors ({}):", described.len());
+                for d in &described {
+                    println!("  {d}");
+                }
+            }
+        }
+
+        let problems = inspection.problems();
+        if problems.is_empty() {
+            println!();
+            println!("The module matches the host ABI.");
+            return Ok(());
+        }
+        // 問題の一覧はエラーとして標準エラー出力に出る
+        Err(RenderError::Abi(problems).into())
+    }
+}
+
+fn print_section(title: &str, entries: &[Entry]) {
+    println!();
+    println!("{title} ({}):", entries.len());
+    let width = entries
+        .iter()
+        .map(|e| e.qualified_name().len())
+        .max()
+        .unwrap_or(0);
+    for entry in entries {
+        let ty = entry.ty.as_deref().unwrap_or("-");
+        println!(
+            "  {:<10} {:<width$}  {ty}",
+            entry.status.to_string(),
+            entry.qualified_name()
+        );
+        if let Status::Mistyped(expected) = &entry.status {
+            println!("  {:<10} {:<width$}  expected {expected}", "", "");
+        }
+    }
+}
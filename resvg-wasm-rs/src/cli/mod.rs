@@ -0,0 +1,345 @@
+//! `resvg-wasm` コマンドの引数定義。
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use resvg_wasm::cache::ModuleCache;
+use resvg_wasm::fonts::FontDatabase;
+use resvg_wasm::resource::{DataUriResolver, DenyAll, FsResolver, ResourceResolver};
+use resvg_wasm::{
+    Color, EncodeOptions, EngineOptions, FitTo, ImageFormat, OptLevel, RecyclePolicy, RenderError,
+    RenderOptions, Renderer, RendererConfig, ResourceLimits,
+};
+
+pub mod batch;
+pub mod bench;
+pub mod compare;
+pub mod export;
+pub mod inspect;
+pub mod precompile;
+pub mod render;
+pub mod serve;
+pub mod trace;
+
+/// resvg の wasm ビルドを使って SVG をラスタライズする。
+#[derive(Debug, Parser)]
+#[command(
+    name = "resvg-wasm",
+    version,
+    after_help = "\
+終了コード: 0 成功, 1 その他の失敗, 2 引数の誤り, 3 wasm の読み込み・リンク・インスタンス化の失敗,
+4 ホストとの ABI の食い違い, 5 ゲストのエラー・トラップ, 6 fuel・タイムアウト・メモリの上限,
+7 ゲストの出力が不正, 8 出力ファイルの書き込みの失敗, 9 基準画像との不一致 (compare)
+
+環境変数 RESVG_WASM_LOG (例: debug, resvg_wasm=trace) で標準エラー出力へのログを有効にする。"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// SVG を 1 枚レンダリングする
+    Render(render::RenderCommand),
+    /// ディレクトリや glob に一致する SVG をまとめてレンダリングする
+    Batch(batch::BatchCommand),
+    /// コンパイル・インスタンス化・レンダリングの時間を計る
+    Bench(bench::BenchCommand),
+    /// レンダリング結果を基準 PNG とピクセル単位で比較する
+    Compare(compare::CompareCommand),
+    /// SVG を複数のサイズ・倍率で書き出す (@2x 画像、favicon.ico、iconset)
+    Export(export::ExportCommand),
+    /// wasm のインポート・エクスポートを一覧し、ホストとの食い違いを報告する
+    Inspect(inspect::InspectCommand),
+    /// wasm を AOT コンパイルして .cwasm に保存する
+    Precompile(precompile::PrecompileCommand),
+    /// POST /render で SVG を PNG に変換する HTTP サーバを起動する
+    Serve(serve::ServeCommand),
+}
+
+/// すべてのサブコマンドで共通の、wasm モジュールの読み込み方。
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// resvg_wasm.wasm (または precompile で作った .cwasm) のパス
+    #[arg(
+        long,
+        global = true,
+        env = "RESVG_WASM",
+        default_value = "resvg_wasm.wasm"
+    )]
+    pub wasm: PathBuf,
+
+    /// コンパイル済みモジュールのキャッシュディレクトリ [既定: ~/.cache/resvg-wasm]
+    #[arg(long, global = true, env = "RESVG_WASM_CACHE", value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// コンパイル済みモジュールのキャッシュを使わない
+    #[arg(long, global = true, conflicts_with = "cache_dir")]
+    pub no_cache: bool,
+
+    /// 1 回のレンダリングで消費できる fuel (おおよその wasm 命令数)
+    #[arg(long, global = true, value_name = "N")]
+    pub fuel: Option<u64>,
+
+    /// 1 回のレンダリングのタイムアウト (秒)
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub timeout: Option<Duration>,
+
+    /// ゲストの線形メモリの上限 (MiB)
+    #[arg(long, global = true, value_name = "MIB")]
+    pub max_memory: Option<usize>,
+
+    /// ゲストのテーブルの要素数の上限
+    #[arg(long, global = true, value_name = "N")]
+    pub max_table_elements: Option<usize>,
+
+    /// 1 つのインスタンスでこの回数レンダリングしたら、インスタンスを作り直す
+    #[arg(long, global = true, value_name = "N")]
+    pub recycle_after: Option<u64>,
+
+    /// ゲストの線形メモリがインスタンス化の直後からこれだけ伸びたら、インスタンスを作り直す (MiB)
+    #[arg(long, global = true, value_name = "MIB")]
+    pub recycle_memory: Option<usize>,
+
+    /// Cranelift の最適化レベル (none, speed, speed-and-size)
+    #[arg(long, global = true, value_name = "LEVEL", default_value_t = OptLevel::Speed)]
+    pub opt_level: OptLevel,
+
+    /// プーリングアロケータで N 個のインスタンス分のメモリを事前に確保する。
+    /// 同時に存在できるインスタンスは N 個までなので、batch の --jobs や
+    /// serve の --workers 以上にする (ワーカーを作る前に最初のインスタンスは解放する)
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub pooling: Option<u32>,
+
+    /// wasm の SIMD 命令を無効にする (relaxed SIMD も無効になる)
+    #[arg(long, global = true)]
+    pub no_simd: bool,
+
+    /// wasm の relaxed SIMD 命令を無効にする
+    #[arg(long, global = true)]
+    pub no_relaxed_simd: bool,
+
+    /// 線形メモリの初期データをコピーオンライトでマップせず、インスタンス化のたびにコピーする
+    #[arg(long, global = true)]
+    pub no_memory_cow: bool,
+
+    /// モジュールを 1 スレッドでコンパイルする
+    #[arg(long, global = true)]
+    pub no_parallel_compilation: bool,
+
+    /// ゲストの DWARF を読み、トラップのバックトレースにソースの位置を付ける
+    #[arg(long, global = true)]
+    pub debug_info: bool,
+
+    /// ゲストがトラップしたら、入力 SVG の隣に wasm のコアダンプ (.coredump) を書き出す
+    #[arg(long, global = true)]
+    pub coredump: bool,
+
+    /// フォントを読み込むディレクトリ (再帰的に探す。複数指定可)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub font_dir: Vec<PathBuf>,
+
+    /// 読み込むフォントファイル (TTF/OTF/TTC。複数指定可)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub font_file: Vec<PathBuf>,
+
+    /// システムのフォントディレクトリからも読み込む
+    #[arg(long, global = true)]
+    pub system_fonts: bool,
+
+    /// 読み込み・リンク・インスタンス化・レンダリングの各段階の span を JSON Lines で書き出す
+    #[arg(long, global = true, value_name = "FILE")]
+    pub trace_json: Option<PathBuf>,
+
+    /// `<image href>` の相対パスをこのディレクトリから読み込む (外には出られない)。
+    /// 省略時は data: URI だけを解決する
+    #[arg(long, global = true, value_name = "DIR")]
+    pub resource_root: Option<PathBuf>,
+
+    /// data: URI も含め、外部リソースをすべて拒否する
+    #[arg(long, global = true, conflicts_with = "resource_root")]
+    pub deny_resources: bool,
+}
+
+impl GlobalArgs {
+    pub fn cache(&self) -> Option<ModuleCache> {
+        if self.no_cache {
+            return None;
+        }
+        self.cache_dir
+            .clone()
+            .or_else(ModuleCache::default_dir)
+            .map(ModuleCache::new)
+    }
+
+    /// フォントを含まない設定。フォントは [`GlobalArgs::fonts`] で別に読み込む。
+    pub fn config(&self) -> anyhow::Result<RendererConfig> {
+        Ok(RendererConfig {
+            engine: EngineOptions {
+                opt_level: self.opt_level,
+                pooling: self.pooling,
+                simd: !self.no_simd,
+                relaxed_simd: !self.no_simd && !self.no_relaxed_simd,
+                memory_init_cow: !self.no_memory_cow,
+                parallel_compilation: !self.no_parallel_compilation,
+                debug_info: self.debug_info,
+                coredump: self.coredump,
+            },
+            limits: ResourceLimits {
+                fuel: self.fuel,
+                timeout: self.timeout,
+                max_memory: self.max_memory.map(|mib| mib.saturating_mul(1024 * 1024)),
+                max_table_elements: self.max_table_elements,
+            },
+            recycle: RecyclePolicy {
+                max_memory_growth: self
+                    .recycle_memory
+                    .map(|mib| mib.saturating_mul(1024 * 1024)),
+                max_renders: self.recycle_after,
+            },
+            cache: self.cache(),
+            fonts: None,
+            resolver: Some(self.resolver()?),
+        })
+    }
+
+    fn resolver(&self) -> anyhow::Result<Arc<dyn ResourceResolver>> {
+        Ok(match &self.resource_root {
+            Some(root) => Arc::new(FsResolver::new(root)?),
+            None if self.deny_resources => Arc::new(DenyAll),
+            None => Arc::new(DataUriResolver),
+        })
+    }
+
+    /// `--font-dir` / `--font-file` / `--system-fonts` のフォントを読み込む。
+    /// どれも指定されていなければ `None`。
+    pub fn fonts(&self) -> anyhow::Result<Option<Arc<FontDatabase>>> {
+        if self.font_dir.is_empty() && self.font_file.is_empty() && !self.system_fonts {
+            return Ok(None);
+        }
+        let mut fonts = FontDatabase::new();
+        for dir in &self.font_dir {
+            fonts.load_dir(dir).map_err(|e| {
+                anyhow::Error::new(e)
+                    .context(format!("failed to load fonts from {}", dir.display()))
+            })?;
+        }
+        for file in &self.font_file {
+            fonts.load_file(file).map_err(|e| {
+                anyhow::Error::new(e).context(format!("failed to load font {}", file.display()))
+            })?;
+        }
+        if self.system_fonts {
+            fonts.load_system_fonts();
+        }
+        if fonts.is_empty() {
+            anyhow::bail!("no fonts were found");
+        }
+        Ok(Some(Arc::new(fonts)))
+    }
+
+    /// `--wasm` のモジュールを読み込み、フォントを登録したレンダラを作る。
+    pub fn renderer(&self) -> anyhow::Result<Renderer> {
+        let config = RendererConfig {
+            fonts: self.fonts()?,
+            ..self.config()?
+        };
+        self.renderer_with(&config)
+    }
+
+    /// `config` で `--wasm` のモジュールを読み込んだレンダラを作る。
+    pub fn renderer_with(&self, config: &RendererConfig) -> anyhow::Result<Renderer> {
+        Renderer::from_file_with(&self.wasm, config).map_err(|e| match e {
+            // 読み込みのエラーはパスを含んでいる
+            RenderError::Load { .. } => anyhow::Error::new(e),
+            e => anyhow::Error::new(e).context(format!("failed to load {}", self.wasm.display())),
+        })
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{e}"))
+}
+
+/// `context_render` に渡すオプション。
+#[derive(Debug, Clone, Args)]
+pub struct RenderFlags {
+    /// 出力の幅 (px)。--height と併用すると両方に収まるように縮尺する [arg4/arg5]
+    #[arg(long, value_name = "PX")]
+    pub width: Option<u32>,
+
+    /// 出力の高さ (px)。--width と併用すると両方に収まるように縮尺する [arg4/arg7]
+    #[arg(long, value_name = "PX")]
+    pub height: Option<u32>,
+
+    /// フィット後に掛けるズーム倍率 [arg6]
+    #[arg(long, default_value_t = 1.0)]
+    pub zoom: f64,
+
+    /// 背景色 (#rgb, #rgba, #rrggbb, #rrggbbaa)。省略時は透明 [arg8]
+    #[arg(long, value_name = "COLOR")]
+    pub background: Option<Color>,
+
+    /// 単位付きの長さを px に変換するときの DPI [arg9]
+    #[arg(long, default_value_t = 96)]
+    pub dpi: u32,
+
+    /// font-size 未指定のテキストに使うフォントサイズ (px) [arg10]
+    #[arg(long, value_name = "PX", default_value_t = 12)]
+    pub font_size: u32,
+
+    /// 出力形式 (png, jpeg, webp, qoi, ppm, rgba)。省略時は出力の拡張子から決め、
+    /// 決まらなければ png
+    #[arg(long, value_name = "FORMAT")]
+    pub format: Option<ImageFormat>,
+
+    /// PNG の圧縮レベル (0-9)。指定するとホスト側でエンコードする
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub compression: Option<u8>,
+
+    /// JPEG の品質 (1-100)
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: u8,
+}
+
+impl RenderFlags {
+    pub fn to_options(&self) -> RenderOptions {
+        let fit_to = match (self.width, self.height) {
+            (None, None) => FitTo::Original,
+            (Some(w), None) => FitTo::Width(w),
+            (None, Some(h)) => FitTo::Height(h),
+            (Some(w), Some(h)) => FitTo::Size(w, h),
+        };
+        RenderOptions {
+            fit_to,
+            zoom: self.zoom,
+            background: self.background,
+            dpi: self.dpi,
+            font_size: self.font_size,
+        }
+    }
+
+    /// 出力のエンコード方法。`--format` がなければ `output` の拡張子から決める。
+    ///
+    /// JPEG と PPM の透明部分は `--background` (省略時は白) で塗る。
+    pub fn encode_options(&self, output: Option<&Path>) -> EncodeOptions {
+        let format = self
+            .format
+            .or_else(|| output.and_then(ImageFormat::from_path))
+            .unwrap_or_default();
+        let defaults = EncodeOptions::default();
+        EncodeOptions {
+            format,
+            png_compression: self.compression,
+            jpeg_quality: self.quality,
+            matte: self.background.unwrap_or(defaults.matte),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use resvg_wasm::cache;
+
+use super::GlobalArgs;
+
+#[derive(Debug, Args)]
+pub struct PrecompileCommand {
+    /// 出力する .cwasm のパス。省略時は --wasm の拡張子を .cwasm にしたもの
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl PrecompileCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| global.wasm.with_extension("cwasm"));
+
+        // レンダラと同じ設定の Engine でコンパイルしないと読み込めない
+        let engine = global.config()?.engine()?;
+        cache::precompile(&engine, &global.wasm, &output)?;
+
+        eprintln!(
+            "Precompiled {} to {}",
+            global.wasm.display(),
+            output.display()
+        );
+        Ok(())
+    }
+}
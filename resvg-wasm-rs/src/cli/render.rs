@@ -0,0 +1,141 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use resvg_wasm::RenderError;
+
+use super::{GlobalArgs, RenderFlags};
+
+#[derive(Debug, Args)]
+pub struct RenderCommand {
+    /// 入力 SVG のパス (`-` で標準入力)
+    pub input: PathBuf,
+
+    /// 出力画像のパス (`-` で標準出力)。省略時は入力の拡張子を出力形式のものにしたもの
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub flags: RenderFlags,
+}
+
+impl RenderCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let output = self.output_path();
+        let options = self.flags.to_options();
+        let encode = self
+            .flags
+            .encode_options((!is_stdio(&output)).then_some(output.as_path()));
+        // wasm のコンパイルより先に、オプションの誤りを報告する
+        options.validate()?;
+        encode.validate()?;
+
+        let svg = read_input(&self.input)?;
+        let mut renderer = global.renderer()?;
+        let image = renderer
+            .render_image(&svg, &options, &encode)
+            .inspect_err(|err| save_coredump(err, &self.input))?;
+
+        if let Some(fonts) = renderer.fonts() {
+            let report = fonts.resolve(&svg);
+            let join = |families: &BTreeSet<String>| {
+                families
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if !report.resolved.is_empty() {
+                eprintln!("Fonts: {}", join(&report.resolved));
+            }
+            if !report.missing.is_empty() {
+                eprintln!("warning: font family not found: {}", join(&report.missing));
+            }
+        }
+
+        write_output(&output, &image.data)?;
+
+        eprintln!(
+            "Rendered {}x{} {} ({} bytes) to {}",
+            image.width,
+            image.height,
+            image.format,
+            image.data.len(),
+            display_path(&output)
+        );
+        Ok(())
+    }
+
+    fn output_path(&self) -> PathBuf {
+        match &self.output {
+            Some(path) => path.clone(),
+            None if is_stdio(&self.input) => PathBuf::from("-"),
+            None => self
+                .input
+                .with_extension(self.flags.format.unwrap_or_default().extension()),
+        }
+    }
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn display_path(path: &Path) -> String {
+    if is_stdio(path) {
+        "<stdout>".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// ファイルまたは標準入力 (`-`) から読み込む。
+pub fn read_input(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut buf = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut buf)?;
+        Ok(buf)
+    } else {
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display()))
+    }
+}
+
+/// `err` にコアダンプ (`--coredump`) があれば、入力の隣に `.coredump` として書き出す。
+///
+/// 標準入力のときはカレントディレクトリの `stdin.coredump` に書く。
+pub fn save_coredump(err: &RenderError, input: &Path) {
+    let Some(coredump) = err.coredump() else {
+        return;
+    };
+    let path = if is_stdio(input) {
+        PathBuf::from("stdin.coredump")
+    } else {
+        input.with_extension("coredump")
+    };
+    match std::fs::write(&path, coredump) {
+        Ok(()) => eprintln!("Wrote coredump to {}", path.display()),
+        Err(e) => eprintln!("warning: failed to write {}: {e}", path.display()),
+    }
+}
+
+/// ファイルまたは標準出力 (`-`) に書き込む。
+pub fn write_output(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    if is_stdio(path) {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(data)
+            .and_then(|()| stdout.flush())
+            .map_err(|error| RenderError::Write {
+                path: "<stdout>".into(),
+                error,
+            })?;
+        Ok(())
+    } else {
+        std::fs::write(path, data).map_err(|error| RenderError::Write {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use clap::Args;
+use resvg_wasm::pool::RenderPool;
+use resvg_wasm::serve::{RenderServer, ServeConfig};
+
+use super::GlobalArgs;
+
+/// --timeout を指定しなかったときの 1 リクエストあたりのレンダリング時間の上限
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Args)]
+#[command(after_help = "\
+エンドポイント:
+  POST /render   SVG を本文で受け取り画像 (既定は image/png) を返す。クエリは render の
+                 フラグと同じ (width, height, zoom, background, dpi, font_size,
+                 format, compression, quality)
+  GET  /healthz  {\"status\":\"ok\"} を返す
+  GET  /metrics  ワーカーごとのゲストの線形メモリのサイズ、レンダリング回数、
+                 インスタンスを作り直した回数 (--recycle-after, --recycle-memory) を返す
+
+エラーは {\"error\": メッセージ, \"kind\": 種別} の JSON で返す。")]
+pub struct ServeCommand {
+    /// 待ち受けるアドレス
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// リクエストを処理するワーカー数 (省略時は CPU 数)
+    #[arg(long, value_name = "N")]
+    pub workers: Option<NonZeroUsize>,
+
+    /// 受け付ける SVG の最大サイズ (バイト)
+    #[arg(long, value_name = "BYTES", default_value_t = 10 * 1024 * 1024)]
+    pub max_body: usize,
+}
+
+impl ServeCommand {
+    pub fn run(&self, global: &GlobalArgs) -> anyhow::Result<()> {
+        let mut config = global.config()?;
+        // サーバでは時間無制限のレンダリングを許さない
+        config.limits.timeout.get_or_insert(DEFAULT_TIMEOUT);
+        config.fonts = global.fonts()?;
+        let renderer = global.renderer_with(&config)?;
+
+        let serve_config = ServeConfig {
+            workers: self.workers.unwrap_or_else(RenderPool::default_jobs),
+            max_body: self.max_body,
+        };
+        let server = RenderServer::bind(&self.listen, renderer, serve_config)?;
+        if let Some(addr) = server.local_addr() {
+            eprintln!("Listening on http://{addr}");
+        }
+        server.run()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! tracing の出力先の設定。
+
+use std::fs::File;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+
+/// 標準エラー出力へのログのフィルタを読む環境変数 (`RUST_LOG` と同じ書式)
+pub const LOG_ENV: &str = "RESVG_WASM_LOG";
+
+/// ログの出力先を設定する。
+///
+/// - 標準エラー出力には [`LOG_ENV`] で指定したレベルのイベントを書く (既定は warn)
+/// - `trace_json` があれば、このクレートのすべての span の終了とイベントを
+///   1 行 1 つの JSON で書く。span の終了には `time.busy` / `time.idle` が付く
+pub fn init(trace_json: Option<&Path>) -> anyhow::Result<()> {
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .with_env_var(LOG_ENV)
+                .from_env_lossy(),
+        );
+
+    let json = match trace_json {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", path.display()))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_span_events(FmtSpan::CLOSE)
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(Mutex::new(file))
+                .with_filter(Targets::new().with_target("resvg_wasm", LevelFilter::TRACE));
+            Some(layer)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(json)
+        .try_init()?;
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use wasmtime::Engine;
+
+use crate::cache::ModuleCache;
+use crate::engine::EngineOptions;
+use crate::fonts::FontDatabase;
+use crate::limits::ResourceLimits;
+use crate::recycle::RecyclePolicy;
+use crate::resource::ResourceResolver;
+use crate::RenderError;
+
+/// [`Renderer`](crate::Renderer) の作り方の設定。
+#[derive(Debug, Clone, Default)]
+pub struct RendererConfig {
+    /// wasmtime の `Engine` の設定
+    pub engine: EngineOptions,
+    /// 1 回のレンダリングに許す資源の上限
+    pub limits: ResourceLimits,
+    /// ゲストのインスタンスを作り直す条件
+    pub recycle: RecyclePolicy,
+    /// wasm のコンパイル結果のキャッシュ
+    pub cache: Option<ModuleCache>,
+    /// インスタンスごとにゲストへ登録するフォント
+    pub fonts: Option<Arc<FontDatabase>>,
+    /// `<image href>` などの外部リソースの解決方法 (`None` ならすべて拒否)
+    pub resolver: Option<Arc<dyn ResourceResolver>>,
+}
+
+impl RendererConfig {
+    /// この設定で動かすための `Engine` を作る。
+    ///
+    /// fuel やタイムアウトはコンパイル時の設定も必要なので、`Engine` ごと作り直す。
+    /// `precompile` の出力もこの `Engine` で作らないと読み込めない。
+    pub fn engine(&self) -> Result<Engine, RenderError> {
+        let mut config = wasmtime::Config::new();
+        self.engine.apply(&mut config, &self.limits)?;
+        config.consume_fuel(self.limits.fuel.is_some());
+        config.epoch_interruption(self.limits.timeout.is_some());
+        Ok(Engine::new(&config)?)
+    }
+}
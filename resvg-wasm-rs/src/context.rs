@@ -0,0 +1,112 @@
+//! 一度パースした SVG を何度もレンダリングするためのハンドル。
+
+use crate::encode::{self, EncodeOptions, EncodedImage, Pixmap};
+use crate::instance::{GuestInstance, Input};
+use crate::{PngInfo, RenderError, RenderOptions, Renderer};
+
+/// [`Renderer::parse`] でパースした SVG。
+///
+/// ゲストが `context_new` をエクスポートしていれば、パース済みのツリーをゲスト側に残し、
+/// [`RenderContext::render`] のたびに `context_render` の arg3 でその ID を渡す。
+/// エクスポートがなければ、毎回 SVG を渡してパースし直す。
+///
+/// ゲストがトラップしてインスタンスが作り直された場合は、次のレンダリングで自動的に
+/// パースし直す。コンテキストは drop 時に `context_free` で解放する。
+pub struct RenderContext<'r> {
+    renderer: &'r mut Renderer,
+    svg: Vec<u8>,
+    dpi: i32,
+    font_size: i32,
+    /// (パースしたインスタンスの番号, コンテキスト ID)
+    handle: Option<(u64, i32)>,
+}
+
+impl<'r> RenderContext<'r> {
+    pub(crate) fn new(
+        renderer: &'r mut Renderer,
+        svg: &[u8],
+        opts: &RenderOptions,
+    ) -> Result<Self, RenderError> {
+        let (_, _, _, _, _, _, dpi, font_size) = opts.to_args()?;
+        let mut context = RenderContext {
+            renderer,
+            svg: svg.to_vec(),
+            dpi,
+            font_size,
+            handle: None,
+        };
+        // パースの失敗はここで報告する
+        context.with_input(|_, _| Ok(()))?;
+        Ok(context)
+    }
+
+    /// ゲストにパース済みのツリーを残せているか (`context_new` があるか)。
+    pub fn is_cached(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// パース済みの SVG をレンダリングし、PNG のバイト列を返す。
+    ///
+    /// `opts` の DPI とフォントサイズは無視され、パース時の値が使われる。
+    pub fn render(&mut self, opts: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+        let args = opts.to_args()?;
+        let png = self.with_input(|guest, input| guest.render(input, args))?;
+        PngInfo::parse(&png)?;
+        Ok(png)
+    }
+
+    /// [`RenderContext::render`] と同じだが、乗算済みアルファの RGBA ピクセルデータを返す。
+    pub fn render_pixmap(&mut self, opts: &RenderOptions) -> Result<Pixmap, RenderError> {
+        let args = opts.to_args()?;
+        self.with_input(|guest, input| guest.render_pixmap(input, args))
+    }
+
+    /// [`Renderer::render_image`] のパース済み版。
+    pub fn render_image(
+        &mut self,
+        opts: &RenderOptions,
+        encode: &EncodeOptions,
+    ) -> Result<EncodedImage, RenderError> {
+        encode.validate()?;
+        if encode.uses_guest_png() {
+            let png = self.render(opts)?;
+            return encode::from_png(png);
+        }
+        let pixmap = self.render_pixmap(opts)?;
+        encode::encode(&pixmap, encode)
+    }
+
+    /// 今のインスタンスでのこの SVG の渡し方を決め、`f` を実行する。
+    fn with_input<T>(
+        &mut self,
+        f: impl FnOnce(&mut GuestInstance, Input) -> Result<T, RenderError>,
+    ) -> Result<T, RenderError> {
+        let (svg, dpi, font_size, handle) = (&self.svg, self.dpi, self.font_size, &mut self.handle);
+        self.renderer.with_guest(|guest| {
+            let id = match *handle {
+                Some((instance, id)) if instance == guest.id() => Some(id),
+                _ => {
+                    let id = guest.parse(svg, dpi, font_size)?;
+                    *handle = id.map(|id| (guest.id(), id));
+                    id
+                }
+            };
+            match id {
+                Some(id) => f(guest, Input::Context(id)),
+                None => f(guest, Input::Svg(svg)),
+            }
+        })
+    }
+}
+
+impl Drop for RenderContext<'_> {
+    fn drop(&mut self) {
+        let Some((instance, id)) = self.handle else {
+            return;
+        };
+        if let Some(guest) = self.renderer.guest_mut().filter(|g| g.id() == instance) {
+            // 解放に失敗しても、ゲストのメモリが少し無駄になるだけ
+            let _ = guest.free_context(id);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! wasm-bindgen の記述子 (descriptor) から、Rust 側の関数のシグネチャを復元する。
+//!
+//! wasm-bindgen の CLI で処理する前の wasm は、`#[wasm_bindgen]` の関数ごとに
+//! `__wbindgen_describe_<名前>` をエクスポートしている。これを呼ぶと
+//! `__wbindgen_describe(u32)` が型を表す数値の列で呼ばれるので、それを集めてデコードする。
+//! CLI で処理済みの wasm (普段の resvg_wasm.wasm) ではこれらは取り除かれている。
+//!
+//! 数値の割り当ては wasm-bindgen 0.2.9x のもの。デコードできない列はそのまま返す。
+
+use std::fmt;
+
+use wasmtime::{Caller, Linker, Module, Store};
+
+use crate::RenderError;
+
+/// 記述子を返すエクスポートの接頭辞。
+pub const DESCRIBE_PREFIX: &str = "__wbindgen_describe_";
+
+/// 記述子の数値を受け取るインポート。
+const DESCRIBE_IMPORT: &str = "__wbindgen_describe";
+
+// wasm-bindgen の src/describe.rs の並び順
+const I8: u32 = 0;
+const U8: u32 = 1;
+const I16: u32 = 2;
+const U16: u32 = 3;
+const I32: u32 = 4;
+const U32: u32 = 5;
+const I64: u32 = 6;
+const U64: u32 = 7;
+const I128: u32 = 8;
+const U128: u32 = 9;
+const F32: u32 = 10;
+const F64: u32 = 11;
+const BOOLEAN: u32 = 12;
+const FUNCTION: u32 = 13;
+const CLOSURE: u32 = 14;
+const CACHED_STRING: u32 = 15;
+const STRING: u32 = 16;
+const REF: u32 = 17;
+const REFMUT: u32 = 18;
+const LONGREF: u32 = 19;
+const SLICE: u32 = 20;
+const VECTOR: u32 = 21;
+const EXTERNREF: u32 = 22;
+const NAMED_EXTERNREF: u32 = 23;
+const ENUM: u32 = 24;
+const STRING_ENUM: u32 = 25;
+const RUST_STRUCT: u32 = 26;
+const CHAR: u32 = 27;
+const OPTIONAL: u32 = 28;
+const RESULT: u32 = 29;
+const UNIT: u32 = 30;
+const CLAMPED: u32 = 31;
+const NONNULL: u32 = 32;
+
+/// Rust 側の型。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    /// `i32` や `bool` のような名前だけで表せる型
+    Primitive(&'static str),
+    /// 構造体や enum、名前付きの JS 型
+    Named(String),
+    Function(Box<Function>),
+    Closure {
+        mutable: bool,
+        function: Box<Function>,
+    },
+    Ref(Box<Descriptor>),
+    RefMut(Box<Descriptor>),
+    Slice(Box<Descriptor>),
+    Vector(Box<Descriptor>),
+    Option(Box<Descriptor>),
+    Result(Box<Descriptor>),
+    Clamped(Box<Descriptor>),
+}
+
+/// 関数の引数と戻り値。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub arguments: Vec<Descriptor>,
+    pub ret: Descriptor,
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Descriptor::Primitive(name) => f.write_str(name),
+            Descriptor::Named(name) => f.write_str(name),
+            Descriptor::Function(function) => write!(f, "fn{function}"),
+            Descriptor::Closure { mutable, function } => {
+                let kind = if *mutable { "FnMut" } else { "Fn" };
+                write!(f, "dyn {kind}{function}")
+            }
+            // &String は wasm-bindgen では &str
+            Descriptor::Ref(inner) if **inner == Descriptor::Primitive("String") => {
+                f.write_str("&str")
+            }
+            Descriptor::Ref(inner) => write!(f, "&{inner}"),
+            Descriptor::RefMut(inner) => write!(f, "&mut {inner}"),
+            Descriptor::Slice(inner) => write!(f, "[{inner}]"),
+            Descriptor::Vector(inner) => write!(f, "Vec<{inner}>"),
+            Descriptor::Option(inner) => write!(f, "Option<{inner}>"),
+            Descriptor::Result(inner) => write!(f, "Result<{inner}, JsValue>"),
+            Descriptor::Clamped(inner) => write!(f, "Clamped<{inner}>"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arguments: Vec<String> = self.arguments.iter().map(|a| a.to_string()).collect();
+        write!(f, "({})", arguments.join(", "))?;
+        if self.ret != Descriptor::Primitive("()") {
+            write!(f, " -> {}", self.ret)?;
+        }
+        Ok(())
+    }
+}
+
+/// 記述子の数値の列をデコードする。
+pub fn decode(words: &[u32]) -> Result<Descriptor, String> {
+    let mut reader = Reader { words, pos: 0 };
+    let descriptor = reader.descriptor()?;
+    if reader.pos != words.len() {
+        return Err(format!(
+            "{} trailing words after {descriptor}",
+            words.len() - reader.pos
+        ));
+    }
+    Ok(descriptor)
+}
+
+struct Reader<'a> {
+    words: &'a [u32],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn next(&mut self) -> Result<u32, String> {
+        let word = self
+            .words
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| format!("unexpected end after {} words", self.pos))?;
+        self.pos += 1;
+        Ok(word)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.next()?;
+        (0..len)
+            .map(|_| {
+                let c = self.next()?;
+                char::from_u32(c).ok_or_else(|| format!("invalid character {c}"))
+            })
+            .collect()
+    }
+
+    fn inner(&mut self) -> Result<Box<Descriptor>, String> {
+        self.descriptor().map(Box::new)
+    }
+
+    fn function(&mut self) -> Result<Function, String> {
+        let _shim = self.next()?;
+        let count = self.next()?;
+        let arguments = (0..count)
+            .map(|_| self.descriptor())
+            .collect::<Result<_, _>>()?;
+        let ret = self.descriptor()?;
+        // async 関数のときの中身の型。表示には使わない
+        let _inner_ret = self.descriptor()?;
+        Ok(Function { arguments, ret })
+    }
+
+    fn descriptor(&mut self) -> Result<Descriptor, String> {
+        use Descriptor::Primitive;
+
+        let tag = self.next()?;
+        Ok(match tag {
+            I8 => Primitive("i8"),
+            U8 => Primitive("u8"),
+            I16 => Primitive("i16"),
+            U16 => Primitive("u16"),
+            I32 => Primitive("i32"),
+            U32 => Primitive("u32"),
+            I64 => Primitive("i64"),
+            U64 => Primitive("u64"),
+            I128 => Primitive("i128"),
+            U128 => Primitive("u128"),
+            F32 => Primitive("f32"),
+            F64 => Primitive("f64"),
+            BOOLEAN => Primitive("bool"),
+            CHAR => Primitive("char"),
+            UNIT => Primitive("()"),
+            CACHED_STRING | STRING => Primitive("String"),
+            EXTERNREF => Primitive("JsValue"),
+            NONNULL => Primitive("NonNull<u8>"),
+            FUNCTION => Descriptor::Function(Box::new(self.function()?)),
+            CLOSURE => {
+                let _shim = self.next()?;
+                let _dtor = self.next()?;
+                let mutable = self.next()? == 1;
+                let tag = self.next()?;
+                if tag != FUNCTION {
+                    return Err(format!("closure without a function (tag {tag})"));
+                }
+                Descriptor::Closure {
+                    mutable,
+                    function: Box::new(self.function()?),
+                }
+            }
+            REF | LONGREF => Descriptor::Ref(self.inner()?),
+            REFMUT => Descriptor::RefMut(self.inner()?),
+            SLICE => Descriptor::Slice(self.inner()?),
+            VECTOR => Descriptor::Vector(self.inner()?),
+            OPTIONAL => Descriptor::Option(self.inner()?),
+            RESULT => Descriptor::Result(self.inner()?),
+            CLAMPED => Descriptor::Clamped(self.inner()?),
+            NAMED_EXTERNREF | RUST_STRUCT => Descriptor::Named(self.string()?),
+            ENUM => {
+                let name = self.string()?;
+                let _hole = self.next()?;
+                Descriptor::Named(name)
+            }
+            STRING_ENUM => {
+                let name = self.string()?;
+                let count = self.next()?;
+                for _ in 0..count {
+                    self.string()?;
+                }
+                let _invalid = self.next()?;
+                Descriptor::Named(name)
+            }
+            tag => return Err(format!("unknown tag {tag} at word {}", self.pos - 1)),
+        })
+    }
+}
+
+/// `__wbindgen_describe_<name>` 1 つ分の記述子。
+#[derive(Debug, Clone)]
+pub struct Described {
+    /// 接頭辞を除いた名前
+    pub name: String,
+    /// `__wbindgen_describe` に渡された数値
+    pub words: Vec<u32>,
+    /// デコード結果。できなければその理由
+    pub descriptor: Result<Descriptor, String>,
+}
+
+impl fmt::Display for Described {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.descriptor {
+            Ok(Descriptor::Function(function)) => write!(f, "fn {}{function}", self.name),
+            Ok(descriptor) => write!(f, "{}: {descriptor}", self.name),
+            Err(reason) => write!(f, "{}: {:?} ({reason})", self.name, self.words),
+        }
+    }
+}
+
+/// `module` の `__wbindgen_describe_*` をすべて実行し、記述子を集める。
+///
+/// 記述子のエクスポートがなければ空の `Vec` を返す。記述子以外のインポートは
+/// 呼ばれるとトラップする関数でつなぐので、描画用のインスタンスとは別に作る。
+pub fn read(module: &Module) -> Result<Vec<Described>, RenderError> {
+    let names: Vec<String> = module
+        .exports()
+        .filter(|e| e.name().starts_with(DESCRIBE_PREFIX) && e.ty().func().is_some())
+        .map(|e| e.name().to_string())
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let engine = module.engine();
+    let mut linker: Linker<Vec<u32>> = Linker::new(engine);
+    linker.allow_shadowing(true);
+    for import in module.imports().filter(|i| i.name() == DESCRIBE_IMPORT) {
+        linker.func_wrap(
+            import.module(),
+            DESCRIBE_IMPORT,
+            |mut caller: Caller<'_, Vec<u32>>, word: i32| caller.data_mut().push(word as u32),
+        )?;
+    }
+    linker.define_unknown_imports_as_traps(module)?;
+
+    let mut store = Store::new(engine, Vec::new());
+    // Engine で fuel やエポックが有効でも止まらないようにする (無効ならどちらも何もしない)
+    let _ = store.set_fuel(u64::MAX);
+    store.set_epoch_deadline(u64::MAX / 2);
+    let instance = linker.instantiate(&mut store, module)?;
+
+    let mut described = Vec::with_capacity(names.len());
+    for export in names {
+        let describe = instance.get_typed_func::<(), ()>(&mut store, &export)?;
+        store.data_mut().clear();
+        describe.call(&mut store, ())?;
+        let words = std::mem::take(store.data_mut());
+        described.push(Described {
+            name: export[DESCRIBE_PREFIX.len()..].to_string(),
+            descriptor: decode(&words),
+            words,
+        });
+    }
+    Ok(described)
+}
@@ -0,0 +1,156 @@
+//! レンダリング結果と基準 PNG のピクセル単位の比較 (ビジュアルリグレッション用)。
+
+use image::{ImageFormat as DecodeFormat, RgbaImage};
+
+use crate::encode::{self, EncodeOptions, EncodedImage, Pixmap};
+use crate::RenderError;
+
+/// 比較の設定。
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// 1 ピクセルあたりの許容差 (0.0-1.0)。アルファを乗算した RGBA のいずれかのチャンネルの差が
+    /// これを超えると不一致 (完全に透明なピクセル同士は RGB が違っても一致する)
+    pub threshold: f64,
+    /// 不一致のピクセルがこの数以下なら合格とする
+    pub max_diff_pixels: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            threshold: 0.1,
+            max_diff_pixels: 0,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn validate(&self) -> Result<(), RenderError> {
+        if !(0.0..=1.0).contains(&self.threshold) {
+            return Err(RenderError::InvalidOptions(format!(
+                "threshold must be in 0.0..=1.0, got {}",
+                self.threshold
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 比較の結果。
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    pub width: u32,
+    pub height: u32,
+    /// 基準画像の大きさ (`width`/`height` と違えばそれだけで不合格)
+    pub reference_size: (u32, u32),
+    /// 許容差を超えたピクセル数
+    pub mismatched: usize,
+    /// 最も大きかったピクセルの差 (0.0-1.0)
+    pub max_delta: f64,
+    /// 不一致のピクセルを赤、一致したピクセルを薄いグレーで描いた画像 (PNG)。
+    /// 大きさが違う場合は `None`
+    pub diff_image: Option<EncodedImage>,
+    passed: bool,
+}
+
+impl DiffReport {
+    /// `max_diff_pixels` 以内に収まったか。
+    pub fn passed(&self) -> bool {
+        self.passed
+    }
+
+    /// 不一致のピクセルの割合。
+    pub fn mismatch_ratio(&self) -> f64 {
+        let total = self.width as usize * self.height as usize;
+        if total == 0 {
+            0.0
+        } else {
+            self.mismatched as f64 / total as f64
+        }
+    }
+}
+
+/// PNG を非乗算の RGBA8 にデコードする。
+pub fn decode_png(png: &[u8]) -> Result<RgbaImage, RenderError> {
+    decode(png).map_err(|e| RenderError::InvalidOutput(format!("failed to decode PNG: {e}")))
+}
+
+fn decode(png: &[u8]) -> image::ImageResult<RgbaImage> {
+    image::load_from_memory_with_format(png, DecodeFormat::Png).map(|image| image.to_rgba8())
+}
+
+/// `actual` と `reference` (どちらも PNG) を比べる。
+pub fn compare(
+    actual: &[u8],
+    reference: &[u8],
+    opts: &DiffOptions,
+) -> Result<DiffReport, RenderError> {
+    opts.validate()?;
+    let actual = decode_png(actual)?;
+    let reference = decode(reference)
+        .map_err(|e| RenderError::InvalidOptions(format!("reference is not a valid PNG: {e}")))?;
+    let (width, height) = actual.dimensions();
+
+    if actual.dimensions() != reference.dimensions() {
+        return Ok(DiffReport {
+            width,
+            height,
+            reference_size: reference.dimensions(),
+            mismatched: width as usize * height as usize,
+            max_delta: 1.0,
+            diff_image: None,
+            passed: false,
+        });
+    }
+
+    let mut mismatched = 0;
+    let mut max_delta: f64 = 0.0;
+    let mut diff = Vec::with_capacity(actual.as_raw().len());
+    for (a, r) in actual.pixels().zip(reference.pixels()) {
+        let delta = channel_delta(a.0, r.0);
+        max_delta = max_delta.max(delta);
+        if delta > opts.threshold {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // 基準画像を白の上で薄くしたグレーで描き、どこが不一致かを見やすくする
+            let [red, green, blue, alpha] = r.0;
+            let luma = (red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000;
+            let luma = (luma * alpha as u32 + 255 * (255 - alpha as u32)) / 255;
+            let faded = (255 - (255 - luma) / 10) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    let diff = Pixmap::new(width, height, diff)?;
+    let diff_image = encode::encode(
+        &diff,
+        &EncodeOptions {
+            png_compression: Some(6),
+            ..EncodeOptions::default()
+        },
+    )?;
+
+    Ok(DiffReport {
+        width,
+        height,
+        reference_size: (width, height),
+        mismatched,
+        max_delta,
+        diff_image: Some(diff_image),
+        passed: mismatched <= opts.max_diff_pixels,
+    })
+}
+
+/// 2 つのピクセルの、アルファを乗算したチャンネルごとの差の最大値 (0.0-1.0)。
+fn channel_delta(a: [u8; 4], b: [u8; 4]) -> f64 {
+    let (a, b) = (premultiply(a), premultiply(b));
+    let max = a.iter().zip(b).map(|(&x, y)| x.abs_diff(y)).max();
+    max.unwrap_or(0) as f64 / 255.0
+}
+
+/// 非乗算の RGBA をアルファ乗算済みにする。見えない色の違いを差に数えないため。
+fn premultiply([red, green, blue, alpha]: [u8; 4]) -> [u8; 4] {
+    let scale = |c: u8| ((c as u32 * alpha as u32 + 127) / 255) as u8;
+    [scale(red), scale(green), scale(blue), alpha]
+}
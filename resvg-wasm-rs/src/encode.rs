@@ -0,0 +1,285 @@
+//! ゲストから受け取ったピクセルデータを、ホスト側で各種画像形式にエンコードする。
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::codecs::qoi::QoiEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+
+use crate::{Color, PngInfo, RenderError};
+
+/// ゲストが返す乗算済みアルファの RGBA ピクセルデータ (resvg の `Pixmap` と同じ並び)。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    /// 1 ピクセル 4 バイト、行の間に詰め物はない
+    pub data: Vec<u8>,
+}
+
+impl Pixmap {
+    /// 大きさとデータ長が合っているかを確かめて `Pixmap` を作る。
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Result<Self, RenderError> {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(4));
+        if width == 0 || height == 0 || expected != Some(data.len()) {
+            return Err(RenderError::InvalidOutput(format!(
+                "pixmap of {width}x{height} does not match {} bytes of data",
+                data.len()
+            )));
+        }
+        Ok(Pixmap {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// 乗算済みアルファを戻した RGBA。
+    pub fn to_straight_rgba(&self) -> Vec<u8> {
+        let mut out = self.data.clone();
+        for px in out.chunks_exact_mut(4) {
+            let a = px[3] as u32;
+            if a != 0 && a != 255 {
+                for c in &mut px[..3] {
+                    *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+                }
+            }
+        }
+        out
+    }
+
+    /// `matte` の上に重ねて不透明にした RGB。
+    pub fn flatten(&self, matte: Color) -> Vec<u8> {
+        let matte = [matte.r, matte.g, matte.b];
+        let mut out = Vec::with_capacity(self.data.len() / 4 * 3);
+        for px in self.data.chunks_exact(4) {
+            let inv = 255 - px[3] as u32;
+            for (c, m) in px[..3].iter().zip(matte) {
+                out.push((*c as u32 + (m as u32 * inv + 127) / 255).min(255) as u8);
+            }
+        }
+        out
+    }
+}
+
+/// 出力する画像形式。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    WebP,
+    Qoi,
+    /// バイナリ PPM (P6)
+    Ppm,
+    /// ヘッダなしの乗算済みアルファ RGBA (ゲストの出力そのまま)
+    Rgba,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 6] = [
+        ImageFormat::Png,
+        ImageFormat::Jpeg,
+        ImageFormat::WebP,
+        ImageFormat::Qoi,
+        ImageFormat::Ppm,
+        ImageFormat::Rgba,
+    ];
+
+    /// 出力パスの拡張子から形式を決める。知らない拡張子なら `None`。
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        ext.parse().ok()
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Qoi => "qoi",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Rgba => "rgba",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Qoi => "image/qoi",
+            ImageFormat::Ppm => "image/x-portable-pixmap",
+            ImageFormat::Rgba => "application/octet-stream",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = RenderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+            "webp" => Ok(ImageFormat::WebP),
+            "qoi" => Ok(ImageFormat::Qoi),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "rgba" | "raw" => Ok(ImageFormat::Rgba),
+            _ => Err(RenderError::InvalidOptions(format!(
+                "unknown image format {s:?}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::WebP => "WebP",
+            ImageFormat::Qoi => "QOI",
+            ImageFormat::Ppm => "PPM",
+            ImageFormat::Rgba => "RGBA",
+        };
+        f.write_str(name)
+    }
+}
+
+/// エンコードの設定。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub format: ImageFormat,
+    /// PNG の圧縮レベル (0-9)。`None` ならゲストがエンコードした PNG をそのまま使う
+    pub png_compression: Option<u8>,
+    /// JPEG の品質 (1-100)
+    pub jpeg_quality: u8,
+    /// アルファを持たない形式 (JPEG, PPM) で、透明部分を塗る色
+    pub matte: Color,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            format: ImageFormat::Png,
+            png_compression: None,
+            jpeg_quality: 90,
+            matte: Color::rgba(0xff, 0xff, 0xff, 0xff),
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// 値の範囲を検証する。
+    pub fn validate(&self) -> Result<(), RenderError> {
+        if self.png_compression.is_some_and(|level| level > 9) {
+            return Err(RenderError::InvalidOptions(format!(
+                "PNG compression level must be in 0..=9, got {}",
+                self.png_compression.unwrap_or_default()
+            )));
+        }
+        if !(1..=100).contains(&self.jpeg_quality) {
+            return Err(RenderError::InvalidOptions(format!(
+                "JPEG quality must be in 1..=100, got {}",
+                self.jpeg_quality
+            )));
+        }
+        Ok(())
+    }
+
+    /// ゲストの PNG 出力をそのまま使えるか (ピクセルデータを取る必要がないか)。
+    pub(crate) fn uses_guest_png(&self) -> bool {
+        self.format == ImageFormat::Png && self.png_compression.is_none()
+    }
+}
+
+/// エンコード済みの画像。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedImage {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// ゲストがエンコードした PNG を検証し、[`EncodedImage`] にする。
+pub(crate) fn from_png(png: Vec<u8>) -> Result<EncodedImage, RenderError> {
+    let info = PngInfo::parse(&png)?;
+    Ok(EncodedImage {
+        format: ImageFormat::Png,
+        width: info.width,
+        height: info.height,
+        data: png,
+    })
+}
+
+/// `pixmap` を `opts` の形式にエンコードする。
+pub fn encode(pixmap: &Pixmap, opts: &EncodeOptions) -> Result<EncodedImage, RenderError> {
+    opts.validate()?;
+    let (width, height) = (pixmap.width, pixmap.height);
+    let mut data = Vec::new();
+    let result = match opts.format {
+        ImageFormat::Png => {
+            let compression = match opts.png_compression {
+                None => CompressionType::Default,
+                Some(0) => CompressionType::Uncompressed,
+                Some(level) => CompressionType::Level(level),
+            };
+            PngEncoder::new_with_quality(&mut data, compression, FilterType::Adaptive).write_image(
+                &pixmap.to_straight_rgba(),
+                width,
+                height,
+                ExtendedColorType::Rgba8,
+            )
+        }
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut data, opts.jpeg_quality)
+            .write_image(
+                &pixmap.flatten(opts.matte),
+                width,
+                height,
+                ExtendedColorType::Rgb8,
+            ),
+        ImageFormat::WebP => WebPEncoder::new_lossless(&mut data).write_image(
+            &pixmap.to_straight_rgba(),
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        ),
+        ImageFormat::Qoi => QoiEncoder::new(&mut data).write_image(
+            &pixmap.to_straight_rgba(),
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        ),
+        ImageFormat::Ppm => PnmEncoder::new(&mut data)
+            .with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary))
+            .write_image(
+                &pixmap.flatten(opts.matte),
+                width,
+                height,
+                ExtendedColorType::Rgb8,
+            ),
+        ImageFormat::Rgba => {
+            data.clone_from(&pixmap.data);
+            Ok(())
+        }
+    };
+    result.map_err(|e| {
+        RenderError::InvalidOutput(format!("failed to encode {}: {e}", opts.format))
+    })?;
+
+    Ok(EncodedImage {
+        format: opts.format,
+        width,
+        height,
+        data,
+    })
+}
@@ -0,0 +1,228 @@
+//! wasmtime の `Engine` の設定 (コード生成の最適化、インスタンスの確保の仕方、wasm の機能)。
+
+use std::fmt;
+use std::str::FromStr;
+
+use wasmtime::{InstanceAllocationStrategy, PoolingAllocationConfig, WasmBacktraceDetails};
+
+use crate::limits::ResourceLimits;
+use crate::RenderError;
+
+/// Cranelift の最適化レベル。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptLevel {
+    /// 最適化しない (コンパイルは最も速い)
+    None,
+    /// 実行速度を優先する (wasmtime の既定)
+    #[default]
+    Speed,
+    /// 実行速度に加えてコードサイズも小さくする
+    SpeedAndSize,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" | "0" => Ok(OptLevel::None),
+            "speed" | "2" => Ok(OptLevel::Speed),
+            "speed-and-size" | "s" => Ok(OptLevel::SpeedAndSize),
+            _ => Err(format!(
+                "unknown opt level {s:?} (expected none, speed or speed-and-size)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed-and-size",
+        })
+    }
+}
+
+/// `Engine` の設定。既定値は wasmtime の既定値と同じ。
+///
+/// SIMD の有無や最適化レベルはコンパイル結果に影響するので、
+/// [`ModuleCache`](crate::cache::ModuleCache) のキーや `.cwasm` の互換性も変わる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOptions {
+    pub opt_level: OptLevel,
+    /// プーリングアロケータで事前に確保しておくインスタンスの数。
+    /// `None` ならインスタンス化のたびに確保する。
+    ///
+    /// 同時に存在できるインスタンスはこの数までなので、[`RenderPool`](crate::pool::RenderPool)
+    /// や [`RenderServer`](crate::serve::RenderServer) のワーカー数以上にする
+    /// (ワーカーを作る前に元のレンダラのインスタンスは解放する)
+    pub pooling: Option<u32>,
+    /// wasm の SIMD 命令
+    pub simd: bool,
+    /// relaxed SIMD 命令 (`simd` が無効なら使えない)
+    pub relaxed_simd: bool,
+    /// 線形メモリの初期データをコピーオンライトでマップする
+    pub memory_init_cow: bool,
+    /// 関数を複数スレッドで並列にコンパイルする
+    pub parallel_compilation: bool,
+    /// DWARF のデバッグ情報を出力し、トラップのバックトレースにソースの位置を付ける
+    /// (ゲストがデバッグ情報付きでビルドされている場合)
+    pub debug_info: bool,
+    /// トラップしたときにコアダンプを取る ([`RenderError::coredump`])
+    pub coredump: bool,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            opt_level: OptLevel::Speed,
+            pooling: None,
+            simd: true,
+            relaxed_simd: true,
+            memory_init_cow: true,
+            parallel_compilation: true,
+            debug_info: false,
+            coredump: false,
+        }
+    }
+}
+
+impl EngineOptions {
+    pub fn validate(&self) -> Result<(), RenderError> {
+        if self.relaxed_simd && !self.simd {
+            return Err(RenderError::InvalidOptions(
+                "relaxed SIMD requires SIMD to be enabled".to_string(),
+            ));
+        }
+        if self.pooling == Some(0) {
+            return Err(RenderError::InvalidOptions(
+                "the instance pool must hold at least 1 instance".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `config` に設定する。プールの各スロットの大きさは `limits` の上限に合わせる。
+    pub(crate) fn apply(
+        &self,
+        config: &mut wasmtime::Config,
+        limits: &ResourceLimits,
+    ) -> Result<(), RenderError> {
+        self.validate()?;
+        config.cranelift_opt_level(match self.opt_level {
+            OptLevel::None => wasmtime::OptLevel::None,
+            OptLevel::Speed => wasmtime::OptLevel::Speed,
+            OptLevel::SpeedAndSize => wasmtime::OptLevel::SpeedAndSize,
+        });
+        // 設定の順序は関係ない。relaxed SIMD だけを有効にする組み合わせは
+        // Engine::new で拒否されるので、validate で先に分かりやすく報告している
+        config.wasm_relaxed_simd(self.relaxed_simd);
+        config.wasm_simd(self.simd);
+        config.memory_init_cow(self.memory_init_cow);
+        config.parallel_compilation(self.parallel_compilation);
+        config.debug_info(self.debug_info);
+        if self.debug_info {
+            config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+        }
+        config.coredump_on_trap(self.coredump);
+
+        if let Some(instances) = self.pooling {
+            let mut pooling = PoolingAllocationConfig::default();
+            // ゲストはメモリとテーブルを 1 つずつしか持たない
+            pooling
+                .total_core_instances(instances)
+                .total_memories(instances)
+                .total_tables(instances);
+            if let Some(max_memory) = limits.max_memory {
+                pooling.max_memory_size(max_memory);
+            }
+            if let Some(max_table_elements) = limits.max_table_elements {
+                pooling.table_elements(max_table_elements);
+            }
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+        }
+        Ok(())
+    }
+
+    /// 設定を 1 つずつ変えたものの一覧 (`bench --compare` 用)。
+    ///
+    /// 名前は変えた設定を `opt-level=none` のように表す。
+    pub fn variants(&self) -> Vec<(String, EngineOptions)> {
+        let mut variants = Vec::new();
+        for opt_level in [OptLevel::None, OptLevel::Speed, OptLevel::SpeedAndSize] {
+            if opt_level != self.opt_level {
+                variants.push((
+                    format!("opt-level={opt_level}"),
+                    EngineOptions {
+                        opt_level,
+                        ..self.clone()
+                    },
+                ));
+            }
+        }
+        let pooling = match self.pooling {
+            Some(_) => None,
+            None => Some(DEFAULT_POOL_SIZE),
+        };
+        variants.push((
+            match pooling {
+                Some(n) => format!("pooling={n}"),
+                None => "pooling=off".to_string(),
+            },
+            EngineOptions {
+                pooling,
+                ..self.clone()
+            },
+        ));
+        // SIMD を切るときは relaxed SIMD も切る
+        let simd = !self.simd;
+        variants.push((
+            format!("simd={}", on_off(simd)),
+            EngineOptions {
+                simd,
+                relaxed_simd: simd && self.relaxed_simd,
+                ..self.clone()
+            },
+        ));
+        if self.simd {
+            variants.push((
+                format!("relaxed-simd={}", on_off(!self.relaxed_simd)),
+                EngineOptions {
+                    relaxed_simd: !self.relaxed_simd,
+                    ..self.clone()
+                },
+            ));
+        }
+        variants.push((
+            format!("memory-cow={}", on_off(!self.memory_init_cow)),
+            EngineOptions {
+                memory_init_cow: !self.memory_init_cow,
+                ..self.clone()
+            },
+        ));
+        variants.push((
+            format!(
+                "parallel-compilation={}",
+                on_off(!self.parallel_compilation)
+            ),
+            EngineOptions {
+                parallel_compilation: !self.parallel_compilation,
+                ..self.clone()
+            },
+        ));
+        variants
+    }
+}
+
+/// [`EngineOptions::variants`] でプーリングを有効にするときのインスタンス数。
+const DEFAULT_POOL_SIZE: u32 = 16;
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use wasmtime::{Trap, WasmBacktrace, WasmCoreDump};
+
+use crate::backtrace::GuestBacktrace;
+use crate::limits::LimitExceeded;
+
+/// レンダリング中に発生するエラー。
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    /// 以下のどれにも当てはまらない wasmtime のエラー
+    #[error(transparent)]
+    Wasm(wasmtime::Error),
+
+    /// wasm ファイルの読み込みまたはコンパイルに失敗した
+    #[error("failed to load {}: {error:#}", path.display())]
+    Load {
+        path: PathBuf,
+        error: wasmtime::Error,
+    },
+
+    /// モジュールのインポートをホスト関数とつなげなかった
+    #[error("failed to link the module's imports: {0:#}")]
+    Link(wasmtime::Error),
+
+    /// インスタンス化 (データセグメントの初期化や start 関数) に失敗した
+    #[error("failed to instantiate the module: {0:#}")]
+    Instantiate(wasmtime::Error),
+
+    /// ホストが使うエクスポートがない
+    #[error("the module does not export `{name}`")]
+    MissingExport { name: String },
+
+    /// エクスポートの型がホストの期待と違う
+    #[error("`{name}` has type {actual}, expected {expected}")]
+    TypeMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// [`abi::check`](crate::abi::check) で見つかった食い違い
+    #[error("the module does not match the host ABI:{}", list(.0))]
+    Abi(Vec<String>),
+
+    /// ゲストの関数を呼んでいる途中でトラップした (ホスト関数のエラーを含む)
+    #[error("guest trapped in `{export}`: {}{}", trap_message(.error), backtrace_suffix(.backtrace))]
+    Trap {
+        export: String,
+        error: wasmtime::Error,
+        /// トラップした時点のゲストのコールスタック
+        backtrace: Option<GuestBacktrace>,
+        /// [`EngineOptions::coredump`](crate::EngineOptions) が有効なら、
+        /// トラップした時点のゲストの状態を wasm のコアダンプ形式でシリアライズしたもの
+        coredump: Option<Vec<u8>>,
+    },
+
+    /// ゲストが返したポインタが線形メモリの範囲外を指している
+    #[error("{what} out of bounds: ptr={ptr} len={len} memory={memory}")]
+    OutOfBounds {
+        what: &'static str,
+        ptr: usize,
+        len: usize,
+        memory: usize,
+    },
+
+    /// ゲストが `__wbindgen_throw` でエラーを投げた (不正な SVG など)
+    #[error("guest error: {0}")]
+    Guest(String),
+
+    /// fuel を使い切った、またはタイムアウトした
+    #[error("render timed out: {0}")]
+    Timeout(String),
+
+    /// 線形メモリまたはテーブルの上限を超えた
+    #[error("{resource} limit exceeded: requested {requested}, limit {limit}")]
+    MemoryLimit {
+        resource: &'static str,
+        limit: usize,
+        requested: usize,
+    },
+
+    /// 入力ファイルの読み込みなど、入出力に失敗した
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// 出力ファイルの書き込みに失敗した
+    #[error("failed to write {}: {error}", path.display())]
+    Write {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    /// レンダリングオプションの値が範囲外
+    #[error("invalid render options: {0}")]
+    InvalidOptions(String),
+
+    /// ゲストが返した出力が PNG として不正
+    #[error("invalid render output: {0}")]
+    InvalidOutput(String),
+}
+
+impl RenderError {
+    /// ゲストの `export` を呼んだときのエラーを変換する。
+    ///
+    /// 上限やゲストのエラーとして分類できないものは [`RenderError::Trap`] にする。
+    pub(crate) fn calling(export: &str) -> impl FnOnce(wasmtime::Error) -> RenderError + '_ {
+        move |err| match RenderError::from(err) {
+            RenderError::Wasm(error) => RenderError::Trap {
+                export: export.to_string(),
+                backtrace: GuestBacktrace::from_error(&error),
+                coredump: None,
+                error,
+            },
+            err => err,
+        }
+    }
+
+    /// トラップした時点のゲストのコールスタック。
+    pub fn backtrace(&self) -> Option<&GuestBacktrace> {
+        match self {
+            RenderError::Trap { backtrace, .. } => backtrace.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// トラップした時点のゲストのコアダンプ (`.coredump` ファイルの中身)。
+    pub fn coredump(&self) -> Option<&[u8]> {
+        match self {
+            RenderError::Trap { coredump, .. } => coredump.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// `path` への書き込みのエラーを [`RenderError::Write`] にする。
+    pub(crate) fn write(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> RenderError {
+        move |error| RenderError::Write {
+            path: path.into(),
+            error,
+        }
+    }
+}
+
+/// バックトレースとコアダンプを除いた、エラーの連鎖。
+fn trap_message(error: &wasmtime::Error) -> String {
+    // wasmtime はどちらも元のエラーの外側に context として付ける
+    let attached = usize::from(error.downcast_ref::<WasmBacktrace>().is_some())
+        + usize::from(error.downcast_ref::<WasmCoreDump>().is_some());
+    error
+        .chain()
+        .skip(attached)
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+fn backtrace_suffix(backtrace: &Option<GuestBacktrace>) -> String {
+    match backtrace {
+        Some(backtrace) if !backtrace.is_empty() => format!("\nwasm backtrace:\n{backtrace}"),
+        _ => String::new(),
+    }
+}
+
+fn list(problems: &[String]) -> String {
+    problems.iter().map(|p| format!("\n  - {p}")).collect()
+}
+
+impl From<wasmtime::Error> for RenderError {
+    fn from(err: wasmtime::Error) -> Self {
+        // ホスト関数や ResourceLimiter から返したエラーは、呼び出し元まで伝わってくる
+        let err = match err.downcast::<GuestThrow>() {
+            Ok(GuestThrow(message)) => return RenderError::Guest(message),
+            Err(err) => err,
+        };
+        // guest.rs のヘルパーが返した分類済みのエラー
+        let err = match err.downcast::<RenderError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        if let Some(exceeded) = err.downcast_ref::<LimitExceeded>() {
+            return RenderError::MemoryLimit {
+                resource: exceeded.resource,
+                limit: exceeded.limit,
+                requested: exceeded.requested,
+            };
+        }
+        match err.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => RenderError::Timeout("fuel exhausted".into()),
+            Some(Trap::Interrupt) => RenderError::Timeout("deadline exceeded".into()),
+            _ => RenderError::Wasm(err),
+        }
+    }
+}
+
+/// `__wbindgen_throw` で受け取ったメッセージ。
+///
+/// ホスト関数がこのエラーを返すとゲストの実行はそこで止まり、
+/// `context_render` の呼び出し結果として [`RenderError::Guest`] に変換される。
+#[derive(Debug)]
+pub(crate) struct GuestThrow(pub(crate) String);
+
+impl fmt::Display for GuestThrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for GuestThrow {}
@@ -0,0 +1,234 @@
+//! ゲストに渡すフォントの読み込みと、SVG が使うフォントファミリの解決状況の確認。
+//!
+//! resvg は `<text>` の描画にフォントが必要だが、wasm のゲストからはファイルシステムが
+//! 見えない。ホスト側で読み込んだフォントファイルを、モジュールがエクスポートする
+//! フォント登録関数 ([`REGISTER_EXPORTS`]) でゲストのフォントデータベースに追加する。
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::RenderError;
+
+/// フォント登録に使うエクスポート名の候補 (先に見つかったものを使う)。
+///
+/// どれも `(ptr: i32, len: i32)` でフォントファイルの中身を受け取り、
+/// 戻り値があれば読み込んだフェイス数 (負ならエラー) として扱う。
+pub const REGISTER_EXPORTS: &[&str] = &["font_register", "register_font", "add_font"];
+
+/// CSS の総称ファミリ。読み込んだフォントのどれかに割り当てられる。
+const GENERIC_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+];
+
+/// よく使われるシステムフォントのディレクトリ。
+const SYSTEM_FONT_DIRS: &[&str] = &[
+    "/usr/share/fonts",
+    "/usr/local/share/fonts",
+    "/System/Library/Fonts",
+    "/Library/Fonts",
+    "C:\\Windows\\Fonts",
+];
+
+/// 読み込んだフォントファイル 1 つ分。
+#[derive(Debug)]
+pub struct FontSource {
+    pub path: PathBuf,
+    pub data: Arc<Vec<u8>>,
+    /// このファイルに含まれるフェイスのファミリ名
+    pub families: Vec<String>,
+}
+
+/// ゲストに渡すフォントの集まり。
+#[derive(Debug, Default)]
+pub struct FontDatabase {
+    sources: Vec<FontSource>,
+}
+
+impl FontDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// フォントファイル (TTF/OTF/TTC/OTC) を 1 つ読み込み、含まれるフェイス数を返す。
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<usize, RenderError> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let families = parse_families(&data).ok_or_else(|| {
+            RenderError::InvalidOptions(format!("{} is not a font file", path.display()))
+        })?;
+        let faces = families.len();
+        self.sources.push(FontSource {
+            path: path.to_path_buf(),
+            data: Arc::new(data),
+            families,
+        });
+        Ok(faces)
+    }
+
+    /// ディレクトリを再帰的に探してフォントファイルを読み込み、フェイス数を返す。
+    ///
+    /// フォントとして読めないファイルは無視する。
+    /// シンボリックリンクで同じディレクトリに戻ってきた場合は 2 回目以降を読まない。
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<usize, RenderError> {
+        self.load_dir_once(dir.as_ref(), &mut HashSet::new())
+    }
+
+    /// `visited` (正規化したパス) にないディレクトリだけを読み込む。
+    fn load_dir_once(
+        &mut self,
+        dir: &Path,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<usize, RenderError> {
+        if !visited.insert(dir.canonicalize()?) {
+            return Ok(0);
+        }
+        let mut faces = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                faces += self.load_dir_once(&path, visited)?;
+            } else if is_font_file(&path) {
+                faces += self.load_file(&path).unwrap_or(0);
+            }
+        }
+        Ok(faces)
+    }
+
+    /// システムのフォントディレクトリから読み込み、フェイス数を返す。
+    ///
+    /// 候補のディレクトリ同士がリンクで重なっていても、同じフォントは 1 回だけ読む。
+    pub fn load_system_fonts(&mut self) -> usize {
+        let mut dirs: Vec<PathBuf> = SYSTEM_FONT_DIRS.iter().map(PathBuf::from).collect();
+        if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+            dirs.push(home.join(".local/share/fonts"));
+            dirs.push(home.join(".fonts"));
+            dirs.push(home.join("Library/Fonts"));
+        }
+        let mut visited = HashSet::new();
+        dirs.iter()
+            .filter(|dir| dir.is_dir())
+            .map(|dir| self.load_dir_once(dir, &mut visited).unwrap_or(0))
+            .sum()
+    }
+
+    pub fn sources(&self) -> &[FontSource] {
+        &self.sources
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// 読み込んだフォントのファミリ名の一覧。
+    pub fn families(&self) -> BTreeSet<&str> {
+        self.sources
+            .iter()
+            .flat_map(|source| source.families.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// `svg` が `font-family` で指定しているファミリを、読み込んだフォントと突き合わせる。
+    pub fn resolve(&self, svg: &[u8]) -> FontReport {
+        let known: BTreeSet<String> = self
+            .families()
+            .into_iter()
+            .map(str::to_ascii_lowercase)
+            .collect();
+
+        let mut report = FontReport::default();
+        for family in referenced_families(svg) {
+            let lower = family.to_ascii_lowercase();
+            let generic = GENERIC_FAMILIES.contains(&lower.as_str());
+            if known.contains(&lower) || (generic && !self.is_empty()) {
+                report.resolved.insert(family);
+            } else {
+                report.missing.insert(family);
+            }
+        }
+        report
+    }
+}
+
+/// 1 回のレンダリングで使われるフォントファミリの解決状況。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FontReport {
+    pub resolved: BTreeSet<String>,
+    pub missing: BTreeSet<String>,
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "ttf" | "otf" | "ttc" | "otc"
+            )
+        })
+}
+
+/// フォントファイルに含まれる各フェイスのファミリ名を読む。フォントでなければ `None`。
+fn parse_families(data: &[u8]) -> Option<Vec<String>> {
+    let count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+    let mut families = Vec::new();
+    for index in 0..count {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let names = face.names();
+        // 拡張ファミリ名 (ID 16) があればそれを、なければファミリ名 (ID 1) を使う
+        let family = [
+            ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+            ttf_parser::name_id::FAMILY,
+        ]
+        .iter()
+        .find_map(|&id| {
+            names
+                .into_iter()
+                .filter(|name| name.name_id == id)
+                .find_map(|name| name.to_string())
+        });
+        if let Some(family) = family {
+            families.push(family);
+        }
+    }
+    Some(families)
+}
+
+/// SVG の `font-family` 属性と style 中の `font-family:` からファミリ名を集める。
+fn referenced_families(svg: &[u8]) -> BTreeSet<String> {
+    let text = String::from_utf8_lossy(svg);
+    let mut families = BTreeSet::new();
+
+    let mut rest = text.as_ref();
+    while let Some(pos) = rest.find("font-family") {
+        rest = rest[pos + "font-family".len()..].trim_start();
+        let value = if let Some(attr) = rest.strip_prefix('=') {
+            // font-family="..." / font-family='...'
+            let attr = attr.trim_start();
+            let Some(quote) = attr.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                continue;
+            };
+            let body = &attr[1..];
+            &body[..body.find(quote).unwrap_or(body.len())]
+        } else if let Some(css) = rest.strip_prefix(':') {
+            // style="font-family: ..." / <style> 内の宣言
+            &css[..css.find([';', '}', '"', '<', '\n']).unwrap_or(css.len())]
+        } else {
+            continue;
+        };
+
+        let value = value.replace("&quot;", "\"").replace("&apos;", "'");
+        for name in value.split(',') {
+            let name = name.trim().trim_matches(['"', '\'']).trim();
+            if !name.is_empty() && name != "inherit" {
+                families.insert(name.to_string());
+            }
+        }
+    }
+    families
+}
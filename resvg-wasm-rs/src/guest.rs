@@ -0,0 +1,185 @@
+//! wasm-bindgen が生成するゲスト側エクスポートとのやりとり。
+
+use wasmtime::{AsContext, AsContextMut, Func, Instance, Memory, Result};
+
+use crate::RenderError;
+
+/// ゲストのアロケータで `len` バイトの領域を確保し、その先頭アドレスを返す。
+///
+/// wasm-bindgen のバージョンによって `__wbindgen_malloc` は `(size)` または
+/// `(size, align)` を取るため、エクスポートの型を見て呼び分ける。
+/// `__wbindgen_malloc` がない場合は `__wbindgen_realloc(0, 0, size, align)` で代用する。
+pub(crate) fn alloc(mut store: impl AsContextMut, instance: &Instance, len: usize) -> Result<i32> {
+    let mut store = store.as_context_mut();
+    let malloc = instance.get_func(&mut store, "__wbindgen_malloc");
+    let realloc = instance.get_func(&mut store, "__wbindgen_realloc");
+    alloc_with(store, malloc, realloc, len)
+}
+
+/// [`alloc`] の本体。ホスト関数の中からも呼べるよう、アロケータは引数で受け取る。
+pub(crate) fn alloc_with(
+    mut store: impl AsContextMut,
+    malloc: Option<Func>,
+    realloc: Option<Func>,
+    len: usize,
+) -> Result<i32> {
+    let mut store = store.as_context_mut();
+    let size = i32::try_from(len).map_err(|_| anyhow::anyhow!("input too large: {len} bytes"))?;
+    let align = 1;
+
+    let ptr = if let Some(malloc) = malloc {
+        match malloc.ty(&store).params().len() {
+            1 => malloc.typed::<i32, i32>(&store)?.call(&mut store, size)?,
+            _ => malloc
+                .typed::<(i32, i32), i32>(&store)?
+                .call(&mut store, (size, align))?,
+        }
+    } else if let Some(realloc) = realloc {
+        match realloc.ty(&store).params().len() {
+            3 => realloc
+                .typed::<(i32, i32, i32), i32>(&store)?
+                .call(&mut store, (0, 0, size))?,
+            _ => realloc
+                .typed::<(i32, i32, i32, i32), i32>(&store)?
+                .call(&mut store, (0, 0, size, align))?,
+        }
+    } else {
+        anyhow::bail!("neither __wbindgen_malloc nor __wbindgen_realloc is exported");
+    };
+
+    if ptr == 0 && size != 0 {
+        anyhow::bail!("guest allocator returned null for {len} bytes");
+    }
+    Ok(ptr)
+}
+
+/// `bytes` をゲストのメモリにコピーし、`(ptr, len)` を返す。
+///
+/// wasm-bindgen の `Vec<u8>` 引数と同じく、確保した領域の所有権はゲストに移る。
+pub(crate) fn upload(
+    mut store: impl AsContextMut,
+    instance: &Instance,
+    memory: &Memory,
+    bytes: &[u8],
+) -> Result<(i32, i32)> {
+    let ptr = alloc(&mut store, instance, bytes.len())?;
+    memory
+        .write(&mut store, ptr as u32 as usize, bytes)
+        .map_err(|_| out_of_bounds(&store, memory, "input buffer", ptr, bytes.len()))?;
+    Ok((ptr, bytes.len() as i32))
+}
+
+/// `context_render` の戻り値が指す出力ディスクリプタを読み、出力バイト列をコピーする。
+///
+/// 戻り値は wasm-bindgen の retptr と同じく、ゲストのメモリ上に置かれた
+/// `[ptr: u32, len: u32]` (リトルエンディアン) の組を指している。
+/// コピー後は `__wbindgen_free` で出力バッファを解放する。
+pub(crate) fn take_output(
+    mut store: impl AsContextMut,
+    instance: &Instance,
+    memory: &Memory,
+    result_ptr: i32,
+) -> Result<Vec<u8>> {
+    let mut store = store.as_context_mut();
+    if result_ptr == 0 {
+        return Err(null_result().into());
+    }
+
+    let mut descriptor = [0u8; 8];
+    memory
+        .read(&store, result_ptr as u32 as usize, &mut descriptor)
+        .map_err(|_| out_of_bounds(&store, memory, "output descriptor", result_ptr, 8))?;
+    let ptr = u32::from_le_bytes(descriptor[0..4].try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(descriptor[4..8].try_into().unwrap()) as usize;
+
+    // 範囲外を指すディスクリプタは、余計なメモリを読まずにエラーにする
+    let end = ptr.saturating_add(len);
+    if ptr == 0 || end > memory.data_size(&store) {
+        return Err(out_of_bounds(
+            &store,
+            memory,
+            "output buffer",
+            ptr as i32,
+            len,
+        ));
+    }
+
+    let mut output = vec![0; len];
+    memory.read(&store, ptr, &mut output)?;
+    free(&mut store, instance, ptr as i32, len as i32)?;
+    Ok(output)
+}
+
+/// [`OUTPUT_PIXMAP`](crate::options::OUTPUT_PIXMAP) 付きの `context_render` の出力を読む。
+///
+/// ディスクリプタは `[ptr, len, width, height]` で、`(width, height, ピクセルデータ)` を返す。
+pub(crate) fn take_pixmap(
+    mut store: impl AsContextMut,
+    instance: &Instance,
+    memory: &Memory,
+    result_ptr: i32,
+) -> Result<(u32, u32, Vec<u8>)> {
+    let mut store = store.as_context_mut();
+    if result_ptr == 0 {
+        return Err(null_result().into());
+    }
+
+    let mut size = [0u8; 8];
+    memory
+        .read(
+            &store,
+            (result_ptr as u32 as usize).saturating_add(8),
+            &mut size,
+        )
+        .map_err(|_| out_of_bounds(&store, memory, "output descriptor", result_ptr, 16))?;
+    let width = u32::from_le_bytes(size[0..4].try_into().unwrap());
+    let height = u32::from_le_bytes(size[4..8].try_into().unwrap());
+    let data = take_output(&mut store, instance, memory, result_ptr)?;
+    Ok((width, height, data))
+}
+
+fn null_result() -> RenderError {
+    RenderError::InvalidOutput("context_render returned a null result".to_string())
+}
+
+/// `ptr..ptr+len` が線形メモリに収まらないことを表すエラー。
+fn out_of_bounds(
+    store: impl AsContext,
+    memory: &Memory,
+    what: &'static str,
+    ptr: i32,
+    len: usize,
+) -> anyhow::Error {
+    RenderError::OutOfBounds {
+        what,
+        ptr: ptr as u32 as usize,
+        len,
+        memory: memory.data_size(&store),
+    }
+    .into()
+}
+
+/// `__wbindgen_free` でゲスト側のバッファを解放する。
+///
+/// `__wbindgen_malloc` と同様に `(ptr, len)` と `(ptr, len, align)` の両方に対応する。
+pub(crate) fn free(
+    mut store: impl AsContextMut,
+    instance: &Instance,
+    ptr: i32,
+    len: i32,
+) -> Result<()> {
+    let mut store = store.as_context_mut();
+    let Some(free) = instance.get_func(&mut store, "__wbindgen_free") else {
+        // 解放関数を持たないモジュールでは、バッファはゲストに任せる
+        return Ok(());
+    };
+    match free.ty(&store).params().len() {
+        2 => free
+            .typed::<(i32, i32), ()>(&store)?
+            .call(&mut store, (ptr, len))?,
+        _ => free
+            .typed::<(i32, i32, i32), ()>(&store)?
+            .call(&mut store, (ptr, len, 1))?,
+    }
+    Ok(())
+}
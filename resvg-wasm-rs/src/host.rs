@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use crate::bindgen::JsHeap;
+use crate::limits::Limiter;
+use crate::resource::ResourceResolver;
+
+/// `Store` ごとに持つホスト側の状態。
+#[derive(Debug, Default)]
+pub struct HostState {
+    /// wasm-bindgen の JS 値ハンドル
+    pub(crate) heap: JsHeap,
+    /// 線形メモリとテーブルの上限
+    pub(crate) limiter: Limiter,
+    /// `resvg_host::resolve_resource` で使うリゾルバ (`None` ならすべて拒否)
+    pub(crate) resolver: Option<Arc<dyn ResourceResolver>>,
+}
@@ -0,0 +1,226 @@
+//! 1 枚の SVG を複数のサイズ・倍率で書き出す (アイコンセット、@2x 画像、favicon)。
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    EncodeOptions, EncodedImage, FitTo, ImageFormat, RenderError, RenderOptions, Renderer,
+};
+
+/// favicon.ico の既定のサイズ。
+pub const ICO_SIZES: &[u32] = &[16, 24, 32, 48, 64, 128, 256];
+
+/// Apple の iconset の既定のサイズ (`icon_512x512@2x.png` = 1024px まで)。
+pub const ICONSET_SIZES: &[u32] = &[16, 32, 64, 128, 256, 512, 1024];
+
+/// iconset のファイル名に使う論理サイズ。
+const ICONSET_POINTS: &[u32] = &[16, 32, 128, 256, 512];
+
+/// 書き出す 1 つのバリエーション。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    /// `n`x`n` に収まるように縮尺する
+    Size(u32),
+    /// 元のサイズに倍率を掛ける (@2x など)
+    Scale(f64),
+}
+
+impl Variant {
+    /// `base` のオプションをこのバリエーション用に書き換える。
+    pub fn apply(&self, base: &RenderOptions) -> RenderOptions {
+        match *self {
+            Variant::Size(n) => RenderOptions {
+                fit_to: FitTo::Size(n, n),
+                ..base.clone()
+            },
+            Variant::Scale(scale) => RenderOptions {
+                zoom: base.zoom * scale,
+                ..base.clone()
+            },
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Size(n) => write!(f, "{n}px"),
+            Variant::Scale(scale) => write!(f, "@{scale}x"),
+        }
+    }
+}
+
+/// レンダリングした 1 つのバリエーション。
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub variant: Variant,
+    pub image: EncodedImage,
+}
+
+/// `svg` を一度だけパースし、`variants` のそれぞれでレンダリングする。
+pub fn render_variants(
+    renderer: &mut Renderer,
+    svg: &[u8],
+    base: &RenderOptions,
+    encode: &EncodeOptions,
+    variants: &[Variant],
+) -> Result<Vec<Rendered>, RenderError> {
+    if variants.is_empty() {
+        return Err(RenderError::InvalidOptions(
+            "no sizes or scales were given".to_string(),
+        ));
+    }
+    for variant in variants {
+        variant.apply(base).validate()?;
+    }
+
+    let mut context = renderer.parse(svg, base)?;
+    variants
+        .iter()
+        .map(|&variant| {
+            let image = context.render_image(&variant.apply(base), encode)?;
+            Ok(Rendered { variant, image })
+        })
+        .collect()
+}
+
+/// 出力ファイル名のテンプレートを展開する。
+///
+/// 使えるプレースホルダ: `{stem}` (入力のファイル名から拡張子を除いたもの), `{size}`,
+/// `{scale}`, `{width}`, `{height}`, `{ext}`。
+/// `{size}` は [`Variant::Size`] のときだけ、`{scale}` は [`Variant::Scale`] のときだけ使える。
+pub fn expand_template(
+    template: &str,
+    stem: &str,
+    rendered: &Rendered,
+) -> Result<PathBuf, RenderError> {
+    let image = &rendered.image;
+    let mut out = template
+        .replace("{stem}", stem)
+        .replace("{width}", &image.width.to_string())
+        .replace("{height}", &image.height.to_string())
+        .replace("{ext}", image.format.extension());
+    match rendered.variant {
+        Variant::Size(n) => out = out.replace("{size}", &n.to_string()),
+        Variant::Scale(scale) => out = out.replace("{scale}", &scale.to_string()),
+    }
+    if let Some(start) = out.find('{').filter(|&i| out[i..].contains('}')) {
+        let end = start + out[start..].find('}').unwrap_or(0);
+        return Err(RenderError::InvalidOptions(format!(
+            "unknown or unusable placeholder {} in {template:?} for {}",
+            &out[start..=end],
+            rendered.variant
+        )));
+    }
+    Ok(PathBuf::from(out))
+}
+
+/// PNG のバリエーションを 1 つの `.ico` にまとめる。
+///
+/// 各画像は PNG のまま格納する (Windows Vista 以降の形式)。256px を超える画像は入れられない。
+pub fn write_ico(images: &[&EncodedImage]) -> Result<Vec<u8>, RenderError> {
+    if images.is_empty() || images.len() > u16::MAX as usize {
+        return Err(RenderError::InvalidOptions(format!(
+            "an .ico must contain 1..={} images, got {}",
+            u16::MAX,
+            images.len()
+        )));
+    }
+    for image in images {
+        if image.format != ImageFormat::Png {
+            return Err(RenderError::InvalidOptions(format!(
+                "an .ico can only contain PNG images, got {}",
+                image.format
+            )));
+        }
+        if image.width > 256 || image.height > 256 {
+            return Err(RenderError::InvalidOptions(format!(
+                "an .ico image must be at most 256x256, got {}x{}",
+                image.width, image.height
+            )));
+        }
+    }
+
+    // ICONDIR (6 バイト) + ICONDIRENTRY (16 バイト) x 枚数 + 画像データ
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(images.len() as u16).to_le_bytes());
+
+    let mut offset = 6 + 16 * images.len();
+    for image in images {
+        // 256px は 0 で表す
+        out.push(image.width as u8);
+        out.push(image.height as u8);
+        out.push(0); // パレットの色数
+        out.push(0); // 予約
+        out.extend_from_slice(&1u16.to_le_bytes()); // プレーン数
+        out.extend_from_slice(&32u16.to_le_bytes()); // ビット深度
+        out.extend_from_slice(&(image.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += image.data.len();
+    }
+    for image in images {
+        out.extend_from_slice(&image.data);
+    }
+    Ok(out)
+}
+
+/// [`write_iconset`] の結果。
+#[derive(Debug, Clone, Default)]
+pub struct IconsetReport {
+    /// 書き出したファイル (名前順)
+    pub written: Vec<PathBuf>,
+    /// iconset に入れられなかったバリエーションと、その理由
+    pub skipped: Vec<(Variant, String)>,
+}
+
+/// PNG のバリエーションを Apple の `.iconset` ディレクトリの命名で書き出す。
+///
+/// `icon_16x16.png`, `icon_16x16@2x.png` (= 32px) のように、正方形の画像だけを
+/// 対応する名前で書き出す。`iconutil -c icns` でそのまま `.icns` にできる。
+/// 正方形でない画像や iconset にないサイズは書き出さず、[`IconsetReport::skipped`] に残す。
+pub fn write_iconset(dir: &Path, rendered: &[Rendered]) -> Result<IconsetReport, RenderError> {
+    std::fs::create_dir_all(dir).map_err(RenderError::write(dir))?;
+    let mut report = IconsetReport::default();
+    for Rendered { variant, image } in rendered {
+        let (px, height) = (image.width, image.height);
+        let skip = if image.format != ImageFormat::Png {
+            Some(format!("{} is not PNG", image.format))
+        } else if px != height {
+            Some(format!("{px}x{height} is not square"))
+        } else {
+            None
+        };
+        if let Some(reason) = skip {
+            report.skipped.push((*variant, reason));
+            continue;
+        }
+
+        let mut names = Vec::new();
+        if ICONSET_POINTS.contains(&px) {
+            names.push(format!("icon_{px}x{px}.png"));
+        }
+        if px % 2 == 0 && ICONSET_POINTS.contains(&(px / 2)) {
+            let pt = px / 2;
+            names.push(format!("icon_{pt}x{pt}@2x.png"));
+        }
+        if names.is_empty() {
+            report
+                .skipped
+                .push((*variant, format!("{px}x{px} is not an iconset size")));
+        }
+        for name in names {
+            let path = dir.join(name);
+            std::fs::write(&path, &image.data).map_err(RenderError::write(&path))?;
+            report.written.push(path);
+        }
+    }
+    if report.written.is_empty() {
+        return Err(RenderError::InvalidOptions(format!(
+            "none of the rendered sizes fit an iconset (use {ICONSET_SIZES:?})"
+        )));
+    }
+    report.written.sort();
+    Ok(report)
+}
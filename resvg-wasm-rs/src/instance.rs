@@ -0,0 +1,384 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::{debug_span, info_span, Span};
+use wasmtime::{
+    Engine, Instance, InstancePre, Memory, Store, TypedFunc, WasmCoreDump, WasmParams, WasmResults,
+};
+
+use crate::abi;
+use crate::bench::RenderTimings;
+use crate::encode::Pixmap;
+use crate::fonts::{FontDatabase, REGISTER_EXPORTS};
+use crate::guest;
+use crate::limits::ResourceLimits;
+use crate::options::{RenderArgs, OUTPUT_PIXMAP};
+use crate::{HostState, PngInfo, RenderError, RendererConfig};
+
+/// `context_render` の引数: (svg_ptr, svg_len, arg3..arg10)
+type ContextRenderParams = (i32, i32, i32, i32, i32, f64, i32, i32, i32, i32);
+
+/// [`GuestInstance::id`] の採番用
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// `context_render` に渡す SVG。
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Input<'a> {
+    /// SVG データそのもの (毎回パースされる)
+    Svg(&'a [u8]),
+    /// `context_new` でパース済みのコンテキスト ID
+    Context(i32),
+}
+
+/// 1 つの `Store` とその中にインスタンス化した resvg モジュール。
+pub(crate) struct GuestInstance {
+    id: u64,
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    context_render: TypedFunc<ContextRenderParams, i32>,
+    limits: ResourceLimits,
+    /// インスタンス化とフォント登録の直後の線形メモリのサイズ
+    baseline: usize,
+    /// `context_render` を呼んだ回数
+    renders: u64,
+    /// 直前のレンダリングの内訳
+    timings: RenderTimings,
+}
+
+impl GuestInstance {
+    /// 新しい `Store` を作り、インポートを解決済みの `pre` からインスタンス化する。
+    ///
+    /// `config` にフォントがあれば、インスタンス化の直後にゲストへ登録する。
+    pub(crate) fn new(
+        engine: &Engine,
+        pre: &InstancePre<HostState>,
+        config: &RendererConfig,
+    ) -> Result<Self, RenderError> {
+        let span = info_span!("instantiate", instance = Empty, memory = Empty).entered();
+        let limits = &config.limits;
+        let state = HostState {
+            limiter: limits.limiter(),
+            resolver: config.resolver.clone(),
+            ..HostState::default()
+        };
+        let mut store = Store::new(engine, state);
+        store.limiter(|state| &mut state.limiter);
+        // インスタンス化 (データセグメントの初期化など) にも同じ上限をかける
+        Self::arm(&mut store, limits)?;
+
+        // WASM モジュールのインスタンス化
+        let instance = pre
+            .instantiate(&mut store)
+            .map_err(|e| match RenderError::from(e) {
+                RenderError::Wasm(e) => RenderError::Instantiate(e),
+                e => e,
+            })?;
+
+        // メモリの取得
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| {
+            RenderError::MissingExport {
+                name: "memory".to_string(),
+            }
+        })?;
+
+        // エクスポートされた `context_render` 関数を取得
+        let context_render =
+            typed_export(&mut store, &instance, "context_render")?.ok_or_else(|| {
+                RenderError::MissingExport {
+                    name: "context_render".to_string(),
+                }
+            })?;
+
+        let mut guest = GuestInstance {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            store,
+            instance,
+            memory,
+            context_render,
+            limits: limits.clone(),
+            baseline: 0,
+            renders: 0,
+            timings: RenderTimings::default(),
+        };
+        if let Some(fonts) = config.fonts.as_deref().filter(|fonts| !fonts.is_empty()) {
+            guest.register_fonts(fonts)?;
+        }
+        guest.baseline = guest.memory_size();
+        span.record("instance", guest.id);
+        span.record("memory", guest.baseline);
+        Ok(guest)
+    }
+
+    /// インスタンスごとに一意な番号。作り直したインスタンスとの区別に使う。
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// 今の線形メモリのサイズ (バイト)。
+    pub(crate) fn memory_size(&self) -> usize {
+        self.memory.data_size(&self.store)
+    }
+
+    /// インスタンス化とフォント登録の直後の線形メモリのサイズ (バイト)。
+    pub(crate) fn baseline(&self) -> usize {
+        self.baseline
+    }
+
+    /// このインスタンスで `context_render` を呼んだ回数。
+    pub(crate) fn renders(&self) -> u64 {
+        self.renders
+    }
+
+    /// 直前のレンダリングの内訳。
+    pub(crate) fn timings(&self) -> RenderTimings {
+        self.timings
+    }
+
+    /// フォントファイルを 1 つずつゲストのフォント登録関数に渡す。
+    fn register_fonts(&mut self, fonts: &FontDatabase) -> Result<(), RenderError> {
+        let Some(register) = REGISTER_EXPORTS
+            .iter()
+            .find_map(|name| self.instance.get_func(&mut self.store, name))
+        else {
+            // フォントが与えられたときだけ必要になる
+            return Err(RenderError::MissingExport {
+                name: REGISTER_EXPORTS.join("` or `"),
+            });
+        };
+        let returns_count = register.ty(&self.store).results().len() == 1;
+        let _span = debug_span!("register_fonts", fonts = fonts.sources().len()).entered();
+
+        for source in fonts.sources() {
+            Self::arm(&mut self.store, &self.limits)?;
+            let (ptr, len) = self.upload(&source.data)?;
+            let call = if returns_count {
+                register
+                    .typed::<(i32, i32), i32>(&self.store)?
+                    .call(&mut self.store, (ptr, len))
+            } else {
+                register
+                    .typed::<(i32, i32), ()>(&self.store)?
+                    .call(&mut self.store, (ptr, len))
+                    .map(|()| 1)
+            };
+            let faces = call.map_err(self.calling("font registration"))?;
+            if faces < 0 {
+                return Err(RenderError::Guest(format!(
+                    "failed to register font {} (code {faces})",
+                    source.path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// fuel とタイムアウトを 1 回分の量に設定し直す。
+    fn arm(store: &mut Store<HostState>, limits: &ResourceLimits) -> Result<(), RenderError> {
+        if let Some(fuel) = limits.fuel {
+            store.set_fuel(fuel)?;
+        }
+        if let Some(ticks) = limits.epoch_ticks() {
+            store.set_epoch_deadline(ticks);
+            store.epoch_deadline_trap();
+        }
+        Ok(())
+    }
+
+    /// `context_new` で SVG をパースし、コンテキスト ID を返す。
+    ///
+    /// モジュールが `context_new` をエクスポートしていなければ `None`。
+    pub(crate) fn parse(
+        &mut self,
+        svg: &[u8],
+        dpi: i32,
+        font_size: i32,
+    ) -> Result<Option<i32>, RenderError> {
+        let Some(context_new) = typed_export::<(i32, i32, i32, i32), i32>(
+            &mut self.store,
+            &self.instance,
+            "context_new",
+        )?
+        else {
+            return Ok(None);
+        };
+        Self::arm(&mut self.store, &self.limits)?;
+        let (svg_ptr, svg_len) = self.upload(svg)?;
+        let id = context_new
+            .call(&mut self.store, (svg_ptr, svg_len, dpi, font_size))
+            .map_err(self.calling("context_new"))?;
+        if id <= 0 {
+            return Err(RenderError::Guest(format!(
+                "context_new failed to parse the SVG (code {id})"
+            )));
+        }
+        Ok(Some(id))
+    }
+
+    /// `context_free` でコンテキストを解放する。
+    pub(crate) fn free_context(&mut self, id: i32) -> Result<(), RenderError> {
+        if let Some(context_free) =
+            typed_export::<i32, ()>(&mut self.store, &self.instance, "context_free")?
+        {
+            Self::arm(&mut self.store, &self.limits)?;
+            context_free
+                .call(&mut self.store, id)
+                .map_err(self.calling("context_free"))?;
+        }
+        Ok(())
+    }
+
+    /// SVG をゲストに渡して `context_render` を呼び、出力バイト列を返す。
+    pub(crate) fn render(
+        &mut self,
+        input: Input,
+        args: RenderArgs,
+    ) -> Result<Vec<u8>, RenderError> {
+        let span = self.render_span(input).entered();
+        let result_ptr = self.call(input, args)?;
+        // レンダリング結果 (PNG バイト列) を読み取り、ゲスト側のバッファを解放する
+        let started = Instant::now();
+        let output = debug_span!("extract_output", format = "png").in_scope(|| {
+            guest::take_output(&mut self.store, &self.instance, &self.memory, result_ptr)
+                .map_err(self.calling("__wbindgen_free"))
+        })?;
+        self.timings.copy = started.elapsed();
+        if let Ok(info) = PngInfo::parse(&output) {
+            span.record("width", info.width);
+            span.record("height", info.height);
+        }
+        span.record("output_size", output.len());
+        span.record("memory", self.memory_size());
+        Ok(output)
+    }
+
+    /// PNG の代わりに乗算済みアルファの RGBA ピクセルデータを返させる。
+    pub(crate) fn render_pixmap(
+        &mut self,
+        input: Input,
+        mut args: RenderArgs,
+    ) -> Result<Pixmap, RenderError> {
+        args.1 |= OUTPUT_PIXMAP;
+        let span = self.render_span(input).entered();
+        let result_ptr = self.call(input, args)?;
+        let started = Instant::now();
+        let (width, height, data) =
+            debug_span!("extract_output", format = "pixmap").in_scope(|| {
+                guest::take_pixmap(&mut self.store, &self.instance, &self.memory, result_ptr)
+                    .map_err(self.calling("__wbindgen_free"))
+            })?;
+        self.timings.copy = started.elapsed();
+        span.record("width", width);
+        span.record("height", height);
+        span.record("output_size", data.len());
+        span.record("memory", self.memory_size());
+        Pixmap::new(width, height, data)
+    }
+
+    /// 1 回のレンダリングの span。出力の大きさとメモリのサイズは終わってから記録する。
+    fn render_span(&self, input: Input) -> Span {
+        let span = info_span!(
+            "render",
+            instance = self.id,
+            svg_size = Empty,
+            context = Empty,
+            width = Empty,
+            height = Empty,
+            output_size = Empty,
+            memory = Empty,
+        );
+        match input {
+            Input::Svg(svg) => span.record("svg_size", svg.len()),
+            Input::Context(id) => span.record("context", id),
+        };
+        span
+    }
+
+    /// `context_render` を呼び、戻り値 (ディスクリプタ) を返す。
+    ///
+    /// SVG データはゲストのメモリに書き込み、コンテキストならその ID を arg3 に渡す。
+    fn call(&mut self, input: Input, args: RenderArgs) -> Result<i32, RenderError> {
+        let (mut arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10) = args;
+        Self::arm(&mut self.store, &self.limits)?;
+        self.renders += 1;
+        self.timings = RenderTimings::default();
+
+        let (svg_ptr, svg_len) = match input {
+            // SVG データをゲストのメモリに書き込む
+            Input::Svg(svg) => {
+                let started = Instant::now();
+                let uploaded =
+                    debug_span!("upload", size = svg.len()).in_scope(|| self.upload(svg))?;
+                self.timings.upload = started.elapsed();
+                uploaded
+            }
+            Input::Context(id) => {
+                arg3 = id;
+                (0, 0)
+            }
+        };
+
+        let started = Instant::now();
+        let _span = debug_span!("context_render").entered();
+        let result_ptr = self
+            .context_render
+            .call(
+                &mut self.store,
+                (
+                    svg_ptr, svg_len, arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10,
+                ),
+            )
+            .map_err(self.calling("context_render"))?;
+        self.timings.call = started.elapsed();
+        Ok(result_ptr)
+    }
+
+    /// ゲストの `export` を呼んだときのエラーを変換する ([`RenderError::calling`])。
+    ///
+    /// トラップにコアダンプが付いていれば、この `Store` の状態を読んでシリアライズする。
+    fn calling<'a>(
+        &'a mut self,
+        export: &'a str,
+    ) -> impl FnOnce(wasmtime::Error) -> RenderError + 'a {
+        move |error| {
+            let mut err = RenderError::calling(export)(error);
+            if let RenderError::Trap {
+                error, coredump, ..
+            } = &mut err
+            {
+                *coredump = error
+                    .downcast_ref::<WasmCoreDump>()
+                    .map(|dump| dump.serialize(&mut self.store, "resvg-wasm"));
+            }
+            err
+        }
+    }
+
+    /// `bytes` をゲストのアロケータで確保した領域にコピーする。
+    fn upload(&mut self, bytes: &[u8]) -> Result<(i32, i32), RenderError> {
+        guest::upload(&mut self.store, &self.instance, &self.memory, bytes)
+            .map_err(self.calling("__wbindgen_malloc"))
+    }
+}
+
+/// `name` のエクスポートを型付きで取得する。ない場合は `None`。
+///
+/// 型が違えば、実際のシグネチャを添えた [`RenderError::TypeMismatch`] を返す。
+fn typed_export<P: WasmParams, R: WasmResults>(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<TypedFunc<P, R>>, RenderError> {
+    let Some(func) = instance.get_func(&mut *store, name) else {
+        return Ok(None);
+    };
+    match func.typed::<P, R>(&*store) {
+        Ok(typed) => Ok(Some(typed)),
+        Err(_) => Err(RenderError::TypeMismatch {
+            name: name.to_string(),
+            expected: abi::expected(name),
+            actual: abi::signature(&func.ty(&*store)),
+        }),
+    }
+}
@@ -0,0 +1,49 @@
+//! resvg の wasm ビルド (`resvg_wasm.wasm`) を wasmtime 上で動かし、SVG を PNG に変換する。
+//!
+//! ```no_run
+//! use resvg_wasm::{RenderOptions, Renderer};
+//!
+//! let mut renderer = Renderer::from_file("resvg_wasm.wasm")?;
+//! let png = renderer.render(&std::fs::read("input.svg")?, &RenderOptions::default())?;
+//! std::fs::write("output.png", png)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod abi;
+pub mod backtrace;
+pub mod batch;
+pub mod bench;
+mod bindgen;
+pub mod cache;
+mod config;
+mod context;
+pub mod describe;
+pub mod diff;
+mod encode;
+mod engine;
+mod error;
+pub mod fonts;
+mod guest;
+mod host;
+pub mod icons;
+mod instance;
+mod limits;
+mod options;
+mod png;
+pub mod pool;
+mod recycle;
+mod renderer;
+pub mod resource;
+pub mod serve;
+
+pub use config::RendererConfig;
+pub use context::RenderContext;
+pub use encode::{encode, EncodeOptions, EncodedImage, ImageFormat, Pixmap};
+pub use engine::{EngineOptions, OptLevel};
+pub use error::RenderError;
+pub use host::HostState;
+pub use limits::ResourceLimits;
+pub use options::{Color, FitTo, RenderOptions};
+pub use png::PngInfo;
+pub use recycle::{MemoryStats, RecyclePolicy};
+pub use renderer::Renderer;
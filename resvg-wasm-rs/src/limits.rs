@@ -0,0 +1,147 @@
+//! 信頼できない SVG をレンダリングするときの CPU・メモリの上限。
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use wasmtime::{Engine, ResourceLimiter, Result};
+
+/// epoch を進める間隔。タイムアウトの精度はこの単位になる。
+pub(crate) const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// 1 回のレンダリングに許す資源の上限。`None` は無制限。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// 1 回のレンダリングで消費できる fuel (おおよそ実行する wasm 命令数)
+    pub fuel: Option<u64>,
+    /// 1 回のレンダリングにかけられる時間 (epoch による割り込み)
+    pub timeout: Option<Duration>,
+    /// ゲストの線形メモリの最大サイズ (バイト)
+    pub max_memory: Option<usize>,
+    /// ゲストのテーブルの最大要素数
+    pub max_table_elements: Option<usize>,
+}
+
+impl ResourceLimits {
+    /// タイムアウトを epoch の tick 数に換算する (最低 1 tick)。
+    pub(crate) fn epoch_ticks(&self) -> Option<u64> {
+        let timeout = self.timeout?;
+        let ticks = timeout.as_nanos().div_ceil(EPOCH_TICK.as_nanos());
+        Some(ticks.clamp(1, u64::MAX as u128) as u64)
+    }
+
+    pub(crate) fn limiter(&self) -> Limiter {
+        Limiter {
+            max_memory: self.max_memory,
+            max_table_elements: self.max_table_elements,
+        }
+    }
+}
+
+/// メモリやテーブルの上限を超えたときにゲストを止めるエラー。
+#[derive(Debug)]
+pub(crate) struct LimitExceeded {
+    pub(crate) resource: &'static str,
+    pub(crate) limit: usize,
+    pub(crate) requested: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} limit of {} exceeded (requested {})",
+            self.resource, self.limit, self.requested
+        )
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// `Store` に設定する `ResourceLimiter`。
+///
+/// 上限を超えた拡張は `false` (ゲストから見て memory.grow の失敗) ではなく、
+/// [`LimitExceeded`] のトラップにする。wasm-bindgen のアロケータは確保に失敗すると
+/// `unreachable` で止まるため、そのままではメモリ不足と区別できないからである。
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    max_memory: Option<usize>,
+    max_table_elements: Option<usize>,
+}
+
+impl ResourceLimiter for Limiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool> {
+        match self.max_memory {
+            Some(limit) if desired > limit => Err(LimitExceeded {
+                resource: "memory",
+                limit,
+                requested: desired,
+            }
+            .into()),
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool> {
+        match self.max_table_elements {
+            Some(limit) if desired > limit => Err(LimitExceeded {
+                resource: "table",
+                limit,
+                requested: desired,
+            }
+            .into()),
+            _ => Ok(true),
+        }
+    }
+}
+
+/// バックグラウンドで `Engine` の epoch を一定間隔で進めるスレッド。
+///
+/// drop すると止まる。同じ `Engine` を共有するレンダラ同士で 1 つを共有する。
+pub(crate) struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    pub(crate) fn start(engine: &Engine) -> Arc<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::Builder::new()
+            .name("resvg-wasm-epoch".into())
+            .spawn({
+                let (engine, stop) = (engine.clone(), stop.clone());
+                move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(EPOCH_TICK);
+                        engine.increment_epoch();
+                    }
+                }
+            })
+            .expect("failed to spawn the epoch ticker thread");
+        Arc::new(EpochTicker {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
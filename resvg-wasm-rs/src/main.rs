@@ -1,66 +1,60 @@
-use wasmtime::*;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // WASM ファイルのパス
-    let wasm_path = "resvg_wasm.wasm";
-
-    // WASM エンジンとストアを初期化
-    let engine = Engine::default();
-    let module = Module::from_file(&engine, wasm_path)?;
-    let mut store = Store::new(&engine, ());
-
-    // Linker を作成して必要な関数を登録
-    let mut linker = Linker::new(&engine);
-
-    // `__wbindgen_placeholder__::__wbindgen_throw` の関数を登録
-    linker.func_wrap(
-        "__wbindgen_placeholder__",
-        "__wbindgen_throw",
-        |_caller: Caller<'_, ()>, ptr: i32, len: i32| {
-            panic!("__wbindgen_throw was called with ptr={} len={}", ptr, len);
-        },
-    )?;
-
-    // WASM モジュールのインスタンス化
-    let instance = linker.instantiate(&mut store, &module)?;
-
-    // メモリの取得
-    let memory = instance
-        .get_memory(&mut store, "memory")
-        .expect("Memory export not found");
-
-    // エクスポートされた `context_render` 関数を取得
-    let context_render = instance
-        .get_typed_func::<(i32, i32, i32, i32, i32, f64, i32, i32, i32, i32), i32>(
-            &mut store,
-            "context_render",
-        )?;
-
-    // 必要な引数を準備
-    let arg1 = 1024; // メモリ内の SVG データ開始位置
-    let arg2 = 100; // SVG データ長
-    let arg3 = 0; // コンテキスト ID
-    let arg4 = 0; // オプションフラグ
-    let arg5 = 0; // 任意の値
-    let arg6 = 0.0; // f64 型の引数
-    let arg7 = 0; // 任意の値
-    let arg8 = 0; // 任意の値
-    let arg9 = 0; // 任意の値
-    let arg10 = 0; // 任意の値
-
-    // `context_render` 関数を呼び出す
-    let result_ptr = context_render.call(
-        &mut store,
-        (arg1, arg2, arg3, arg4, arg5, arg6, arg7, arg8, arg9, arg10),
-    )?;
-
-    // レンダリング結果を読み取る
-    let mut buffer = vec![0; 1024 * 1024]; // 1MB のバッファ
-    memory.read(&store, result_ptr as usize, &mut buffer)?;
-
-    // PNG ファイルに保存
-    std::fs::write("output.png", buffer)?;
+use std::process::ExitCode;
+
+use clap::Parser;
+use resvg_wasm::RenderError;
+
+mod cli;
+
+use cli::compare::Regression;
+use cli::{Cli, Command};
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(err) = cli::trace::init(cli.global.trace_json.as_deref()) {
+        eprintln!("error: {err:#}");
+        return ExitCode::FAILURE;
+    }
+
+    let result = match &cli.command {
+        Command::Render(cmd) => cmd.run(&cli.global),
+        Command::Batch(cmd) => cmd.run(&cli.global),
+        Command::Bench(cmd) => cmd.run(&cli.global),
+        Command::Compare(cmd) => cmd.run(&cli.global),
+        Command::Export(cmd) => cmd.run(&cli.global),
+        Command::Inspect(cmd) => cmd.run(&cli.global),
+        Command::Precompile(cmd) => cmd.run(&cli.global),
+        Command::Serve(cmd) => cmd.run(&cli.global),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            exit_code(&err)
+        }
+    }
+}
 
-    println!("Rendered PNG saved to output.png");
-    Ok(())
+/// エラーの種類ごとの終了コード (一覧は `--help` の末尾を参照)。
+fn exit_code(err: &anyhow::Error) -> ExitCode {
+    if err.is::<Regression>() {
+        return ExitCode::from(9);
+    }
+    let Some(err) = err.downcast_ref::<RenderError>() else {
+        return ExitCode::FAILURE;
+    };
+    let code = match err {
+        // clap と同じ
+        RenderError::InvalidOptions(_) => 2,
+        RenderError::Load { .. } | RenderError::Link(_) | RenderError::Instantiate(_) => 3,
+        RenderError::MissingExport { .. }
+        | RenderError::TypeMismatch { .. }
+        | RenderError::Abi(_) => 4,
+        RenderError::Guest(_) | RenderError::Trap { .. } => 5,
+        RenderError::Timeout(_) | RenderError::MemoryLimit { .. } => 6,
+        RenderError::InvalidOutput(_) | RenderError::OutOfBounds { .. } => 7,
+        RenderError::Write { .. } => 8,
+        RenderError::Wasm(_) | RenderError::Io(_) => 1,
+    };
+    ExitCode::from(code)
 }
@@ -0,0 +1,208 @@
+//! `context_render` に渡すレンダリングオプションと、その ABI への変換。
+//!
+//! `context_render` は次の 10 引数を取る:
+//!
+//! | 引数    | 型    | 内容                                                   |
+//! |---------|-------|--------------------------------------------------------|
+//! | `arg1`  | `i32` | SVG データの先頭アドレス                               |
+//! | `arg2`  | `i32` | SVG データ長                                           |
+//! | `arg3`  | `i32` | コンテキスト ID (0 = 使わない)                         |
+//! | `arg4`  | `i32` | フィットモード (0: 元のサイズ, 1: 幅, 2: 高さ, 3: 両方) と出力フラグ |
+//! | `arg5`  | `i32` | フィット先の幅 (px, モード 1 / 3 のみ)                 |
+//! | `arg6`  | `f64` | フィット後に掛けるズーム倍率                           |
+//! | `arg7`  | `i32` | フィット先の高さ (px, モード 2 / 3 のみ)               |
+//! | `arg8`  | `i32` | 背景色 `0xRRGGBBAA` (0 = 透明)                         |
+//! | `arg9`  | `i32` | DPI                                                    |
+//! | `arg10` | `i32` | デフォルトのフォントサイズ (px)                        |
+//!
+//! 同じ SVG を何度もレンダリングする場合は、ゲストがエクスポートしていれば
+//! `context_new(svg_ptr, svg_len, dpi, font_size) -> i32` で一度だけパースし、
+//! 返ってきた ID (正の値) を `arg3` に渡す。このとき `arg1`/`arg2` は 0 で、
+//! `arg9`/`arg10` はパース時の値が使われる。不要になったら `context_free(id)` で解放する。
+//!
+//! `arg4` に [`OUTPUT_PIXMAP`] を立てると、ゲストは PNG にエンコードせず乗算済みアルファの
+//! RGBA ピクセルデータを返す。このとき戻り値のディスクリプタは `[ptr, len, width, height]`
+//! の 4 ワードになる。
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::RenderError;
+
+/// 出力画像の幅・高さの上限 (px)。
+pub const MAX_DIMENSION: u32 = 16384;
+/// ズーム倍率の上限。
+pub const MAX_ZOOM: f64 = 64.0;
+/// DPI の上限。
+pub const MAX_DPI: u32 = 9600;
+/// デフォルトのフォントサイズの上限 (px)。
+pub const MAX_FONT_SIZE: u32 = 1024;
+
+/// `arg4` の出力フラグ: PNG の代わりに RGBA ピクセルデータを返させる。
+pub(crate) const OUTPUT_PIXMAP: i32 = 0x100;
+
+/// SVG を出力サイズに合わせる方法。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FitTo {
+    /// SVG に書かれたサイズのまま
+    #[default]
+    Original,
+    /// 幅を合わせ、高さはアスペクト比を保って決める
+    Width(u32),
+    /// 高さを合わせ、幅はアスペクト比を保って決める
+    Height(u32),
+    /// 幅と高さの両方に収まるように縮尺する
+    Size(u32, u32),
+}
+
+/// `0xRRGGBBAA` 形式の色。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    /// `0xRRGGBBAA` にパックした値。
+    pub const fn to_u32(self) -> u32 {
+        u32::from_be_bytes([self.r, self.g, self.b, self.a])
+    }
+}
+
+impl FromStr for Color {
+    type Err = RenderError;
+
+    /// `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa` 形式の文字列を解釈する。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RenderError::InvalidOptions(format!("invalid color {s:?}"));
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let digit =
+            |i: usize, n: usize| u8::from_str_radix(&hex[i..i + n], 16).map_err(|_| invalid());
+
+        match hex.len() {
+            3 | 4 => {
+                let short = |i| digit(i, 1).map(|v| v * 0x11);
+                let a = if hex.len() == 4 { short(3)? } else { 0xff };
+                Ok(Color::rgba(short(0)?, short(1)?, short(2)?, a))
+            }
+            6 | 8 => {
+                let a = if hex.len() == 8 { digit(6, 2)? } else { 0xff };
+                Ok(Color::rgba(digit(0, 2)?, digit(2, 2)?, digit(4, 2)?, a))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:08x}", self.to_u32())
+    }
+}
+
+/// `context_render` に渡すレンダリングオプション。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// 出力サイズの決め方
+    pub fit_to: FitTo,
+    /// フィット後に掛けるズーム倍率
+    pub zoom: f64,
+    /// 背景色 (`None` なら透明)
+    pub background: Option<Color>,
+    /// 単位付きの長さ (`mm`, `in` など) を px に変換するときの DPI
+    pub dpi: u32,
+    /// `font-size` が指定されていないテキストのフォントサイズ (px)
+    pub font_size: u32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        // resvg (usvg::Options) のデフォルト値に合わせる
+        RenderOptions {
+            fit_to: FitTo::Original,
+            zoom: 1.0,
+            background: None,
+            dpi: 96,
+            font_size: 12,
+        }
+    }
+}
+
+/// `context_render` の SVG 以外の引数 (arg3..arg10)。
+pub(crate) type RenderArgs = (i32, i32, i32, f64, i32, i32, i32, i32);
+
+impl RenderOptions {
+    /// 値の範囲を検証する。
+    pub fn validate(&self) -> Result<(), RenderError> {
+        let invalid = |msg: String| Err(RenderError::InvalidOptions(msg));
+        let check_dimension = |name: &str, value: u32| {
+            if value == 0 || value > MAX_DIMENSION {
+                invalid(format!(
+                    "{name} must be in 1..={MAX_DIMENSION}, got {value}"
+                ))
+            } else {
+                Ok(())
+            }
+        };
+
+        match self.fit_to {
+            FitTo::Original => {}
+            FitTo::Width(w) => check_dimension("width", w)?,
+            FitTo::Height(h) => check_dimension("height", h)?,
+            FitTo::Size(w, h) => {
+                check_dimension("width", w)?;
+                check_dimension("height", h)?;
+            }
+        }
+        if !self.zoom.is_finite() || self.zoom <= 0.0 || self.zoom > MAX_ZOOM {
+            return invalid(format!(
+                "zoom must be in (0, {MAX_ZOOM}], got {}",
+                self.zoom
+            ));
+        }
+        if self.dpi == 0 || self.dpi > MAX_DPI {
+            return invalid(format!("dpi must be in 1..={MAX_DPI}, got {}", self.dpi));
+        }
+        if self.font_size == 0 || self.font_size > MAX_FONT_SIZE {
+            return invalid(format!(
+                "font size must be in 1..={MAX_FONT_SIZE}, got {}",
+                self.font_size
+            ));
+        }
+        Ok(())
+    }
+
+    /// 検証したうえで、ゲストの ABI に合わせた引数列に変換する。
+    pub(crate) fn to_args(&self) -> Result<RenderArgs, RenderError> {
+        self.validate()?;
+
+        let context_id = 0;
+        let (mode, width, height) = match self.fit_to {
+            FitTo::Original => (0, 0, 0),
+            FitTo::Width(w) => (1, w, 0),
+            FitTo::Height(h) => (2, 0, h),
+            FitTo::Size(w, h) => (3, w, h),
+        };
+        let background = self.background.map_or(0, Color::to_u32);
+
+        Ok((
+            context_id,
+            mode,
+            width as i32,
+            self.zoom,
+            height as i32,
+            background as i32,
+            self.dpi as i32,
+            self.font_size as i32,
+        ))
+    }
+}
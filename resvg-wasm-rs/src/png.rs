@@ -0,0 +1,86 @@
+use crate::RenderError;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// PNG の IHDR チャンクから読み取った画像情報。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+}
+
+impl PngInfo {
+    /// PNG シグネチャと IHDR チャンクを検証し、画像情報を返す。
+    pub fn parse(data: &[u8]) -> Result<Self, RenderError> {
+        let invalid = |msg: String| Err(RenderError::InvalidOutput(msg));
+
+        // シグネチャ (8) + 長さ (4) + 種別 (4) + IHDR 本体 (13) + CRC (4)
+        if data.len() < 33 {
+            return invalid(format!(
+                "output is too short to be a PNG ({} bytes)",
+                data.len()
+            ));
+        }
+        if data[..8] != SIGNATURE {
+            return invalid("output does not start with the PNG signature".into());
+        }
+
+        let length = u32::from_be_bytes(data[8..12].try_into().unwrap());
+        if length != 13 || &data[12..16] != b"IHDR" {
+            return invalid("first PNG chunk is not a valid IHDR".into());
+        }
+        let ihdr = &data[16..29];
+        let crc = u32::from_be_bytes(data[29..33].try_into().unwrap());
+        if crc != crc32(&data[12..29]) {
+            return invalid("IHDR checksum mismatch".into());
+        }
+
+        let width = u32::from_be_bytes(ihdr[0..4].try_into().unwrap());
+        let height = u32::from_be_bytes(ihdr[4..8].try_into().unwrap());
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return invalid(format!("invalid PNG dimensions {width}x{height}"));
+        }
+
+        // ビット深度とカラータイプの組み合わせ (PNG 仕様 11.2.2)
+        let (bit_depth, color_type) = (ihdr[8], ihdr[9]);
+        let valid = match color_type {
+            0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+            2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+            _ => false,
+        };
+        if !valid {
+            return invalid(format!(
+                "invalid PNG bit depth {bit_depth} for color type {color_type}"
+            ));
+        }
+        if ihdr[10] != 0 || ihdr[11] != 0 || ihdr[12] > 1 {
+            return invalid("unsupported PNG compression, filter or interlace method".into());
+        }
+
+        Ok(PngInfo {
+            width,
+            height,
+            bit_depth,
+            color_type,
+        })
+    }
+}
+
+/// PNG チャンクの CRC-32 (ISO 3309) を計算する。
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
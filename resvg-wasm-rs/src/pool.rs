@@ -0,0 +1,99 @@
+//! 1 つのコンパイル済みモジュールを共有し、スレッドごとの `Store` で並列にレンダリングする。
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use crate::batch::{self, BatchItem, BatchOutcome, BatchReport};
+use crate::{EncodeOptions, RenderError, RenderOptions, Renderer};
+
+/// ワーカースレッドのプール。
+///
+/// 各ワーカーは [`Renderer::fork`] で作った自分専用の `Store`/`Instance` を持ち、
+/// `Engine`・`Module`・`InstancePre` はすべてのワーカーで共有する。
+pub struct RenderPool {
+    template: Renderer,
+    jobs: NonZeroUsize,
+}
+
+impl RenderPool {
+    /// `template` のモジュールを使い、`jobs` 個のワーカーで動くプールを作る。
+    pub fn new(template: Renderer, jobs: NonZeroUsize) -> Self {
+        RenderPool { template, jobs }
+    }
+
+    /// 利用可能な CPU 数。
+    pub fn default_jobs() -> NonZeroUsize {
+        std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+    }
+
+    pub fn jobs(&self) -> NonZeroUsize {
+        self.jobs
+    }
+
+    /// `items` を並列にレンダリングして `encode` の形式で書き出す。
+    ///
+    /// 処理の完了順にかかわらず、`on_done` の呼び出しと [`BatchReport`] の並びは
+    /// `items` と同じ順になる。`on_done` は呼び出し元のスレッドで呼ばれる。
+    pub fn render_batch(
+        &mut self,
+        items: &[BatchItem],
+        opts: &RenderOptions,
+        encode: &EncodeOptions,
+        mut on_done: impl FnMut(&BatchOutcome),
+    ) -> Result<BatchReport, RenderError> {
+        let jobs = self.jobs.get().min(items.len());
+        if jobs <= 1 {
+            return Ok(batch::render_batch(
+                &mut self.template,
+                items,
+                opts,
+                encode,
+                on_done,
+            ));
+        }
+
+        // テンプレートのインスタンスは使わないので、プールの枠をワーカーに譲る
+        self.template.release_guest();
+        // ワーカーごとの Store はここで作り、インスタンス化の失敗はまとめて返す
+        let workers = (0..jobs)
+            .map(|_| self.template.fork())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::channel();
+        let mut slots: Vec<Option<BatchOutcome>> = items.iter().map(|_| None).collect();
+        let mut report = BatchReport::default();
+
+        std::thread::scope(|scope| {
+            for mut renderer in workers {
+                let (next, tx) = (&next, tx.clone());
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else { break };
+                    let outcome = BatchOutcome {
+                        item: item.clone(),
+                        result: batch::render_one(&mut renderer, item, opts, encode),
+                    };
+                    if tx.send((index, outcome)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            // 完了したものを入力順に並べ直して通知する
+            for (index, outcome) in rx {
+                slots[index] = Some(outcome);
+                while let Some(outcome) =
+                    slots.get_mut(report.outcomes.len()).and_then(Option::take)
+                {
+                    on_done(&outcome);
+                    report.outcomes.push(outcome);
+                }
+            }
+        });
+
+        Ok(report)
+    }
+}
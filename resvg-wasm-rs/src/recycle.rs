@@ -0,0 +1,47 @@
+//! ゲストの線形メモリの監視と、インスタンスの作り直し。
+//!
+//! wasm の線形メモリは伸びるだけで縮まないので、ゲスト側でリークや断片化があると
+//! 長く動かすほどメモリを使い続ける。[`RecyclePolicy`] の条件を満たしたら、
+//! 次のレンダリングの前に `InstancePre` から新しいインスタンスを作り直す。
+
+/// インスタンスを作り直す条件。`None` はその条件を使わない。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecyclePolicy {
+    /// 線形メモリがインスタンス化の直後からこのバイト数以上伸びたら作り直す
+    pub max_memory_growth: Option<usize>,
+    /// 1 つのインスタンスでこの回数レンダリングしたら作り直す
+    pub max_renders: Option<u64>,
+}
+
+impl RecyclePolicy {
+    /// `stats` の今のインスタンスを作り直すべきか。
+    pub(crate) fn should_recycle(&self, stats: &MemoryStats) -> bool {
+        self.max_memory_growth
+            .is_some_and(|max| stats.growth() >= max)
+            || self.max_renders.is_some_and(|max| stats.renders >= max)
+    }
+}
+
+/// [`Renderer`](crate::Renderer) のゲストのメモリの使用状況。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// 今のインスタンスの線形メモリのサイズ (バイト)。インスタンスがなければ 0
+    pub current: usize,
+    /// 今のインスタンスの、インスタンス化とフォント登録の直後のサイズ
+    pub baseline: usize,
+    /// これまでのすべてのインスタンスでの最大サイズ
+    pub peak: usize,
+    /// 今のインスタンスでのレンダリング回数
+    pub renders: u64,
+    /// すべてのインスタンスでのレンダリング回数の合計
+    pub total_renders: u64,
+    /// [`RecyclePolicy`] によって作り直した回数 (トラップによる作り直しは含まない)
+    pub recycles: u64,
+}
+
+impl MemoryStats {
+    /// インスタンス化の直後から伸びた分 (バイト)。
+    pub fn growth(&self) -> usize {
+        self.current.saturating_sub(self.baseline)
+    }
+}
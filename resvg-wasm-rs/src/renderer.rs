@@ -0,0 +1,290 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tracing::{debug, info_span};
+use wasmtime::{Engine, InstancePre, Linker, Module};
+
+use crate::abi;
+use crate::bench::RenderTimings;
+use crate::bindgen;
+use crate::cache::{self, ModuleCache};
+use crate::context::RenderContext;
+use crate::encode::{self, EncodeOptions, EncodedImage, Pixmap};
+use crate::fonts::FontDatabase;
+use crate::instance::{GuestInstance, Input};
+use crate::limits::EpochTicker;
+use crate::recycle::MemoryStats;
+use crate::resource;
+use crate::{HostState, PngInfo, RenderError, RenderOptions, RendererConfig};
+
+/// resvg の wasm モジュールを保持し、SVG を PNG にレンダリングする。
+///
+/// モジュールのコンパイルとインスタンス化は生成時に一度だけ行い、
+/// 以降の [`Renderer::render`] では同じインスタンスを使い回す。
+/// ゲストがトラップした場合は、次のレンダリングの前にインスタンスを作り直す。
+///
+/// `Renderer` は 1 つの `Store` を持つのでスレッド間で共有はできない。
+/// 複数スレッドで使う場合は [`Renderer::fork`] でスレッドごとに複製する。
+pub struct Renderer {
+    engine: Engine,
+    module: Module,
+    linker: Linker<HostState>,
+    instance_pre: InstancePre<HostState>,
+    config: RendererConfig,
+    ticker: Option<Arc<EpochTicker>>,
+    guest: Option<GuestInstance>,
+    /// インスタンスをまたいで集計するメモリの使用状況
+    stats: MemoryStats,
+    /// 直前のレンダリングの内訳
+    timings: RenderTimings,
+}
+
+impl Renderer {
+    /// `path` の wasm ファイル (または `precompile` で作った `.cwasm`) を読み込んでレンダラを作る。
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RenderError> {
+        Self::from_file_with(path, &RendererConfig::default())
+    }
+
+    /// [`Renderer::from_file`] と同じだが、wasm のコンパイル結果を `cache` に保存して使い回す。
+    pub fn from_file_cached(
+        path: impl AsRef<Path>,
+        cache: &ModuleCache,
+    ) -> Result<Self, RenderError> {
+        let config = RendererConfig {
+            cache: Some(cache.clone()),
+            ..RendererConfig::default()
+        };
+        Self::from_file_with(path, &config)
+    }
+
+    /// `config` に従って `path` のモジュールを読み込み、レンダラを作る。
+    pub fn from_file_with(
+        path: impl AsRef<Path>,
+        config: &RendererConfig,
+    ) -> Result<Self, RenderError> {
+        let engine = config.engine()?;
+        let module = cache::load_module(&engine, path.as_ref(), config.cache.as_ref())?;
+        Self::with_config(engine, module, config)
+    }
+
+    /// wasm のバイナリ (またはテキスト形式) からレンダラを作る。
+    pub fn from_bytes(wasm: &[u8]) -> Result<Self, RenderError> {
+        Self::from_bytes_with(wasm, &RendererConfig::default())
+    }
+
+    /// `config` に従って wasm のバイナリ (またはテキスト形式) からレンダラを作る。
+    pub fn from_bytes_with(wasm: &[u8], config: &RendererConfig) -> Result<Self, RenderError> {
+        let engine = config.engine()?;
+        let module = Module::new(&engine, wasm)?;
+        Self::with_config(engine, module, config)
+    }
+
+    /// コンパイル済みのモジュールからレンダラを作る。
+    pub fn new(engine: Engine, module: Module) -> Result<Self, RenderError> {
+        Self::with_config(engine, module, &RendererConfig::default())
+    }
+
+    /// `config` に従ってコンパイル済みのモジュールからレンダラを作る。
+    ///
+    /// `engine` は [`RendererConfig::engine`] で作ったものでなければならない。
+    pub fn with_config(
+        engine: Engine,
+        module: Module,
+        config: &RendererConfig,
+    ) -> Result<Self, RenderError> {
+        let span = info_span!("link", imports = module.imports().len()).entered();
+        // シグネチャの食い違いは、インスタンス化より先に分かりやすく報告する
+        abi::check(&module)?;
+        let linker = Self::linker_for(&engine, &module)?;
+        // インポートの解決はここで一度だけ行い、以降のインスタンス化で使い回す
+        let instance_pre = linker.instantiate_pre(&module).map_err(RenderError::Link)?;
+        drop(span);
+        let ticker = config.limits.timeout.map(|_| EpochTicker::start(&engine));
+        let guest = GuestInstance::new(&engine, &instance_pre, config)?;
+
+        Ok(Renderer {
+            engine,
+            module,
+            linker,
+            instance_pre,
+            config: config.clone(),
+            ticker,
+            stats: MemoryStats {
+                peak: guest.memory_size(),
+                ..MemoryStats::default()
+            },
+            guest: Some(guest),
+            timings: RenderTimings::default(),
+        })
+    }
+
+    /// `Engine`・コンパイル済みモジュール・`InstancePre` を共有し、
+    /// 自分専用の `Store` を持つ新しいレンダラを作る。
+    ///
+    /// モジュールの再コンパイルは行わないので、ワーカースレッドごとに安価に作れる。
+    pub fn fork(&self) -> Result<Self, RenderError> {
+        let guest = GuestInstance::new(&self.engine, &self.instance_pre, &self.config)?;
+        Ok(Renderer {
+            engine: self.engine.clone(),
+            module: self.module.clone(),
+            linker: self.linker.clone(),
+            instance_pre: self.instance_pre.clone(),
+            config: self.config.clone(),
+            ticker: self.ticker.clone(),
+            stats: MemoryStats {
+                peak: guest.memory_size(),
+                ..MemoryStats::default()
+            },
+            guest: Some(guest),
+            timings: RenderTimings::default(),
+        })
+    }
+
+    /// ゲストが必要とするホスト関数を登録した `Linker` を作る。
+    fn linker_for(engine: &Engine, module: &Module) -> Result<Linker<HostState>, RenderError> {
+        // Linker を作成し、wasm-bindgen のインポートとホスト関数をすべて登録する
+        let mut linker = Linker::new(engine);
+        bindgen::link(&mut linker, module).map_err(RenderError::Link)?;
+        resource::link(&mut linker).map_err(RenderError::Link)?;
+        Ok(linker)
+    }
+
+    /// SVG をレンダリングし、PNG のバイト列を返す。
+    pub fn render(&mut self, svg: &[u8], opts: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+        // オプションは SVG をゲストに渡す前に検証する
+        let args = opts.to_args()?;
+        let png = self.with_guest(|guest| guest.render(Input::Svg(svg), args))?;
+        PngInfo::parse(&png)?;
+        Ok(png)
+    }
+
+    /// SVG をレンダリングし、乗算済みアルファの RGBA ピクセルデータを返す。
+    pub fn render_pixmap(
+        &mut self,
+        svg: &[u8],
+        opts: &RenderOptions,
+    ) -> Result<Pixmap, RenderError> {
+        let args = opts.to_args()?;
+        self.with_guest(|guest| guest.render_pixmap(Input::Svg(svg), args))
+    }
+
+    /// SVG をレンダリングし、`encode` の形式でエンコードする。
+    ///
+    /// 圧縮レベルを指定しない PNG はゲストのエンコーダをそのまま使い、
+    /// それ以外はピクセルデータを受け取ってホスト側でエンコードする。
+    pub fn render_image(
+        &mut self,
+        svg: &[u8],
+        opts: &RenderOptions,
+        encode: &EncodeOptions,
+    ) -> Result<EncodedImage, RenderError> {
+        encode.validate()?;
+        if encode.uses_guest_png() {
+            let png = self.render(svg, opts)?;
+            return encode::from_png(png);
+        }
+        let pixmap = self.render_pixmap(svg, opts)?;
+        encode::encode(&pixmap, encode)
+    }
+
+    /// SVG を一度だけパースし、サイズや倍率を変えて何度もレンダリングできるようにする。
+    ///
+    /// パースには `opts` の DPI とフォントサイズを使う。
+    pub fn parse(
+        &mut self,
+        svg: &[u8],
+        opts: &RenderOptions,
+    ) -> Result<RenderContext<'_>, RenderError> {
+        RenderContext::new(self, svg, opts)
+    }
+
+    /// 今のゲストのインスタンス (なければ `None`)。
+    pub(crate) fn guest_mut(&mut self) -> Option<&mut GuestInstance> {
+        self.guest.as_mut()
+    }
+
+    /// 今のゲストのインスタンスを捨てる。次のレンダリングの前に作り直す。
+    ///
+    /// プーリングアロケータではインスタンスの数に上限があるので、
+    /// このレンダラから [`Renderer::fork`] でワーカーを作る前に呼ぶ。
+    pub(crate) fn release_guest(&mut self) {
+        self.guest = None;
+    }
+
+    /// ゲストのインスタンスで `f` を実行する。インスタンスがなければ作る。
+    ///
+    /// 実行後にメモリの使用状況を記録し、[`RecyclePolicy`](crate::RecyclePolicy) の条件を
+    /// 満たしていればインスタンスを捨てる (次の呼び出しで作り直す)。
+    pub(crate) fn with_guest<T>(
+        &mut self,
+        f: impl FnOnce(&mut GuestInstance) -> Result<T, RenderError>,
+    ) -> Result<T, RenderError> {
+        let guest = match &mut self.guest {
+            Some(guest) => guest,
+            guest => guest.insert(GuestInstance::new(
+                &self.engine,
+                &self.instance_pre,
+                &self.config,
+            )?),
+        };
+
+        let renders = guest.renders();
+        let result = f(guest);
+        self.stats.total_renders += guest.renders() - renders;
+        self.stats.peak = self.stats.peak.max(guest.memory_size());
+        self.timings = guest.timings();
+
+        if let Err(error) = &result {
+            // トラップ後のゲストの状態は信用できないので、インスタンスを捨てる
+            debug!(%error, "discarding the guest instance");
+            self.guest = None;
+        } else if self.config.recycle.should_recycle(&self.memory_stats()) {
+            let stats = self.memory_stats();
+            debug!(
+                renders = stats.renders,
+                memory = stats.current,
+                growth = stats.growth(),
+                "recycling the guest instance"
+            );
+            self.guest = None;
+            self.stats.recycles += 1;
+        }
+        result
+    }
+
+    /// 直前のレンダリングで、ゲストとのやり取りにかかった時間の内訳。
+    pub fn last_timings(&self) -> RenderTimings {
+        self.timings
+    }
+
+    /// ゲストの線形メモリの使用状況。
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = self.stats;
+        if let Some(guest) = &self.guest {
+            stats.current = guest.memory_size();
+            stats.baseline = guest.baseline();
+            stats.renders = guest.renders();
+        }
+        stats
+    }
+
+    /// ゲストに登録したフォント。
+    pub fn fonts(&self) -> Option<&FontDatabase> {
+        self.config.fonts.as_deref()
+    }
+
+    /// レンダラが使っている wasmtime の `Engine`。
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// コンパイル済みの resvg モジュール。
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// ゲストの関数を登録済みの `Linker`。
+    pub fn linker(&self) -> &Linker<HostState> {
+        &self.linker
+    }
+}
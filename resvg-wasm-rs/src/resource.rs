@@ -0,0 +1,198 @@
+//! `<image href>` などの外部リソースをホスト側で解決する仕組み。
+//!
+//! ゲストは外部リソースが必要になると、次のインポートを呼ぶ:
+//!
+//! ```text
+//! (import "resvg_host" "resolve_resource" (func (param i32 i32 i32)))
+//! ```
+//!
+//! 引数は `(href_ptr, href_len, retptr)` で、ホストは [`ResourceResolver`] で解決した
+//! 中身をゲストのアロケータで確保した領域にコピーし、`retptr` に `[ptr, len]` を書き込む。
+//! 解決できない・許可しない場合は `[0, 0]` を書き込み、ゲストはそのリソースを描画しない。
+//! 確保した領域の所有権はゲストに移る (wasm-bindgen の `Option<Vec<u8>>` の戻り値と同じ)。
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use base64::Engine as _;
+use wasmtime::{Caller, Linker, Result};
+
+use crate::bindgen;
+use crate::{HostState, RenderError};
+
+/// インポートのモジュール名。
+pub const HOST_MODULE: &str = "resvg_host";
+
+/// [`HOST_MODULE`] からインポートできる関数とそのシグネチャ。
+pub(crate) const HOST_FUNCTIONS: &[(&str, &str)] = &[("resolve_resource", "(i32, i32, i32) -> ()")];
+
+/// 1 つのリソースとして読み込める最大サイズ (バイト)。
+pub const MAX_RESOURCE_SIZE: usize = 64 * 1024 * 1024;
+
+/// `href` をリソースの中身に解決する。
+///
+/// レンダリング中にゲストから呼ばれる。[`Renderer::fork`](crate::Renderer::fork) した
+/// レンダラやサーバのワーカーで共有されるので `Send + Sync` が必要。
+pub trait ResourceResolver: fmt::Debug + Send + Sync {
+    /// `href` の中身を返す。解決できない・許可しない場合は `None`。
+    fn resolve(&self, href: &str) -> Option<Vec<u8>>;
+}
+
+/// すべてのリソースを拒否する。信頼できない SVG を扱うとき用。
+///
+/// [`RendererConfig::resolver`](crate::RendererConfig::resolver) が `None` のときもこれと同じ。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DenyAll;
+
+impl ResourceResolver for DenyAll {
+    fn resolve(&self, _href: &str) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// `data:` URI だけを解決する。ホストのファイルには触れない。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DataUriResolver;
+
+impl ResourceResolver for DataUriResolver {
+    fn resolve(&self, href: &str) -> Option<Vec<u8>> {
+        decode_data_uri(href)
+    }
+}
+
+/// `root` 以下のファイルと `data:` URI を解決する。
+///
+/// 相対パスと `file:` URI は `root` からのパスとして扱い、`..` やシンボリックリンクで
+/// `root` の外に出るものは拒否する。`http:` などその他のスキームは解決しない。
+#[derive(Debug, Clone)]
+pub struct FsResolver {
+    root: PathBuf,
+}
+
+impl FsResolver {
+    /// `root` を基準にするリゾルバを作る。`root` は存在するディレクトリでなければならない。
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, RenderError> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| {
+            RenderError::InvalidOptions(format!("invalid resource root {}: {e}", root.display()))
+        })?;
+        if !root.is_dir() {
+            return Err(RenderError::InvalidOptions(format!(
+                "resource root {} is not a directory",
+                root.display()
+            )));
+        }
+        Ok(FsResolver { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `href` を `root` 以下の実在するファイルのパスにする。外に出るものは `None`。
+    fn path_for(&self, href: &str) -> Option<PathBuf> {
+        let path = match href.strip_prefix("file://") {
+            Some(rest) => percent_decode(rest)?,
+            None if has_scheme(href) => return None,
+            None => percent_decode(href)?,
+        };
+        let path = String::from_utf8(path).ok()?;
+        // 絶対パスも root からの相対パスとして扱う
+        let candidate = self.root.join(path.trim_start_matches('/'));
+        let candidate = candidate.canonicalize().ok()?;
+        (candidate.starts_with(&self.root) && candidate.is_file()).then_some(candidate)
+    }
+}
+
+impl ResourceResolver for FsResolver {
+    fn resolve(&self, href: &str) -> Option<Vec<u8>> {
+        if href.starts_with("data:") {
+            return decode_data_uri(href);
+        }
+        let path = self.path_for(href)?;
+        if std::fs::metadata(&path).ok()?.len() > MAX_RESOURCE_SIZE as u64 {
+            return None;
+        }
+        std::fs::read(path).ok()
+    }
+}
+
+/// `resvg_host::resolve_resource` を `linker` に登録する。
+pub(crate) fn link(linker: &mut Linker<HostState>) -> Result<()> {
+    linker.func_wrap(
+        HOST_MODULE,
+        "resolve_resource",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32, retptr: i32| -> Result<()> {
+            let href = read_href(&mut caller, ptr, len)?;
+            let resolver = caller.data().resolver.clone();
+            let data = resolver
+                .and_then(|resolver| resolver.resolve(&href))
+                .filter(|data| data.len() <= MAX_RESOURCE_SIZE);
+            bindgen::write_bytes_ret(&mut caller, retptr, data.as_deref())
+        },
+    )?;
+    Ok(())
+}
+
+fn read_href(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String> {
+    let memory = bindgen::memory(caller)?;
+    let data = memory.data(&*caller);
+    let start = ptr as u32 as usize;
+    let len = len as u32 as usize;
+    if len > MAX_RESOURCE_SIZE {
+        anyhow::bail!("href too long: {len} bytes");
+    }
+    let Some(bytes) = data.get(start..start.saturating_add(len)) else {
+        anyhow::bail!(
+            "href out of bounds: ptr={start} len={len} memory={}",
+            data.len()
+        );
+    };
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// `http:` や `data:` のような URI スキームで始まるか。
+fn has_scheme(href: &str) -> bool {
+    match href.split_once(':') {
+        // Windows のドライブレター (`C:\...`) はスキームとみなさない
+        Some((scheme, _)) => {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// `data:[<mediatype>][;base64],<data>` の中身をデコードする。
+fn decode_data_uri(href: &str) -> Option<Vec<u8>> {
+    let rest = href.strip_prefix("data:")?;
+    let (header, data) = rest.split_once(',')?;
+    if header.split(';').any(|param| param.trim() == "base64") {
+        // SVG の属性値には改行や空白が入りうる
+        let mut data = percent_decode(data)?;
+        data.retain(|b| !b.is_ascii_whitespace());
+        base64::engine::general_purpose::STANDARD.decode(data).ok()
+    } else {
+        percent_decode(data)
+    }
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,365 @@
+//! `POST /render` で SVG を受け取り PNG などの画像を返す HTTP サーバ。
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{debug, warn};
+
+use crate::pool::RenderPool;
+use crate::{
+    Color, EncodeOptions, EncodedImage, FitTo, MemoryStats, RenderError, RenderOptions, Renderer,
+};
+
+/// サーバの設定。
+#[derive(Debug, Clone)]
+pub struct ServeConfig {
+    /// リクエストを処理するワーカー数 (= プールするインスタンス数)
+    pub workers: NonZeroUsize,
+    /// 受け付ける SVG の最大サイズ (バイト)
+    pub max_body: usize,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            workers: RenderPool::default_jobs(),
+            max_body: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// レンダリング用の HTTP サーバ。
+///
+/// ワーカーはそれぞれ [`Renderer::fork`] で作った自分専用のインスタンスを持ち、
+/// リクエストをまたいで使い回す。レンダリングの時間やメモリの上限は `template` の
+/// [`RendererConfig`](crate::RendererConfig) で設定する。
+pub struct RenderServer {
+    server: Arc<Server>,
+    template: Renderer,
+    config: ServeConfig,
+    stop: Arc<AtomicBool>,
+    /// ワーカーごとの、直近のリクエストを処理した後のメモリの使用状況
+    metrics: Mutex<Vec<MemoryStats>>,
+}
+
+/// 別スレッドから [`RenderServer::run`] を止めるためのハンドル。
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    server: Arc<Server>,
+    stop: Arc<AtomicBool>,
+    workers: usize,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // recv() で待っているワーカーを 1 つずつ起こす
+        for _ in 0..self.workers {
+            self.server.unblock();
+        }
+    }
+}
+
+impl RenderServer {
+    /// `addr` で待ち受けるサーバを作る。ポート 0 を指定すると空いているポートを使う。
+    pub fn bind(addr: &str, template: Renderer, config: ServeConfig) -> Result<Self, RenderError> {
+        let server = Server::http(addr).map_err(|e| {
+            RenderError::Io(std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                format!("failed to listen on {addr}: {e}"),
+            ))
+        })?;
+        Ok(RenderServer {
+            server: Arc::new(server),
+            template,
+            config,
+            stop: Arc::new(AtomicBool::new(false)),
+            metrics: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 実際に待ち受けているアドレス。
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            server: self.server.clone(),
+            stop: self.stop.clone(),
+            workers: self.config.workers.get(),
+        }
+    }
+
+    /// [`ShutdownHandle::shutdown`] が呼ばれるまでリクエストを処理する。
+    pub fn run(mut self) -> Result<(), RenderError> {
+        // テンプレートのインスタンスは使わないので、プールの枠をワーカーに譲る
+        self.template.release_guest();
+        let workers = (0..self.config.workers.get())
+            .map(|_| self.template.fork())
+            .collect::<Result<Vec<_>, _>>()?;
+        *self.metrics.lock().unwrap() = workers.iter().map(Renderer::memory_stats).collect();
+
+        let this = &self;
+        std::thread::scope(|scope| {
+            for (index, mut renderer) in workers.into_iter().enumerate() {
+                scope.spawn(move || {
+                    while !this.stop.load(Ordering::SeqCst) {
+                        match this.server.recv() {
+                            Ok(request) => {
+                                this.handle(&mut renderer, request);
+                                this.metrics.lock().unwrap()[index] = renderer.memory_stats();
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn handle(&self, renderer: &mut Renderer, mut request: Request) {
+        let (path, query) = match request.url().split_once('?') {
+            Some((path, query)) => (path.to_string(), query.to_string()),
+            None => (request.url().to_string(), String::new()),
+        };
+
+        let response = match (request.method(), path.as_str()) {
+            (Method::Get, "/healthz") => json_response(200, &json!({ "status": "ok" })),
+            (Method::Get, "/metrics") => json_response(200, &self.metrics_json()),
+            (Method::Post, "/render") => match self.render(renderer, &mut request, &query) {
+                Ok((image, missing_fonts)) => {
+                    let mut response = Response::from_data(image.data)
+                        .with_header(content_type(image.format.mime_type()));
+                    if !missing_fonts.is_empty() {
+                        match header("X-Missing-Fonts", &missing_fonts) {
+                            Ok(header) => response.add_header(header),
+                            Err(error) => warn!(%error, "dropping the X-Missing-Fonts header"),
+                        }
+                    }
+                    response.boxed()
+                }
+                Err((status, kind, message)) => {
+                    json_response(status, &json!({ "error": message, "kind": kind }))
+                }
+            },
+            (_, "/healthz" | "/metrics" | "/render") => json_response(
+                405,
+                &json!({ "error": "method not allowed", "kind": "method" }),
+            ),
+            _ => json_response(404, &json!({ "error": "not found", "kind": "not_found" })),
+        };
+
+        // クライアントが切断していても、ワーカーは次のリクエストに進む
+        let _ = request.respond(response);
+    }
+
+    /// `GET /metrics` の本文。
+    fn metrics_json(&self) -> serde_json::Value {
+        let metrics = self.metrics.lock().unwrap();
+        let workers: Vec<_> = metrics
+            .iter()
+            .map(|stats| {
+                json!({
+                    "memory_bytes": stats.current,
+                    "baseline_bytes": stats.baseline,
+                    "growth_bytes": stats.growth(),
+                    "peak_bytes": stats.peak,
+                    "renders": stats.renders,
+                    "total_renders": stats.total_renders,
+                    "recycles": stats.recycles,
+                })
+            })
+            .collect();
+        json!({
+            "memory_bytes": metrics.iter().map(|s| s.current).sum::<usize>(),
+            "total_renders": metrics.iter().map(|s| s.total_renders).sum::<u64>(),
+            "recycles": metrics.iter().map(|s| s.recycles).sum::<u64>(),
+            "workers": workers,
+        })
+    }
+
+    fn render(
+        &self,
+        renderer: &mut Renderer,
+        request: &mut Request,
+        query: &str,
+    ) -> Result<(EncodedImage, String), (u16, &'static str, String)> {
+        let (options, encode) = options_from_query(query).map_err(error_response)?;
+
+        let limit = self.config.max_body;
+        if request.body_length().is_some_and(|len| len > limit) {
+            return Err(too_large(limit));
+        }
+        let mut svg = Vec::new();
+        request
+            .as_reader()
+            .take(limit as u64 + 1)
+            .read_to_end(&mut svg)
+            .map_err(|e| (400, "request", format!("failed to read body: {e}")))?;
+        if svg.len() > limit {
+            return Err(too_large(limit));
+        }
+
+        let image = renderer
+            .render_image(&svg, &options, &encode)
+            .map_err(error_response)?;
+        // 見つからなかったフォントファミリは X-Missing-Fonts ヘッダで知らせる
+        let missing_fonts = renderer
+            .fonts()
+            .map(|fonts| {
+                let missing = fonts.resolve(&svg).missing;
+                // ヘッダに載せられない (ASCII 以外を含む) ファミリ名は省く
+                missing
+                    .into_iter()
+                    .filter(|family| family.is_ascii() && !family.contains(['\r', '\n']))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default();
+        Ok((image, missing_fonts))
+    }
+}
+
+fn too_large(limit: usize) -> (u16, &'static str, String) {
+    (
+        413,
+        "too_large",
+        format!("request body exceeds {limit} bytes"),
+    )
+}
+
+/// レンダリングのエラーを HTTP ステータスとエラー種別に対応づける。
+fn error_response(err: RenderError) -> (u16, &'static str, String) {
+    let (status, kind) = match &err {
+        RenderError::InvalidOptions(_) => (400, "invalid_options"),
+        RenderError::Guest(_) => (422, "guest"),
+        RenderError::InvalidOutput(_) => (422, "invalid_output"),
+        RenderError::Timeout(_) => (422, "timeout"),
+        RenderError::MemoryLimit { .. } => (422, "memory_limit"),
+        RenderError::Trap { .. } => (422, "trap"),
+        RenderError::OutOfBounds { .. } => (422, "invalid_output"),
+        RenderError::Load { .. }
+        | RenderError::Link(_)
+        | RenderError::Instantiate(_)
+        | RenderError::MissingExport { .. }
+        | RenderError::TypeMismatch { .. }
+        | RenderError::Abi(_) => (500, "module"),
+        RenderError::Wasm(_) | RenderError::Io(_) | RenderError::Write { .. } => (500, "internal"),
+    };
+    // バックトレースなどを含む全文はログにだけ書く
+    if status == 400 {
+        debug!(kind, error = %err, "rejected a render request");
+    } else {
+        warn!(kind, error = %err, "render request failed");
+    }
+    // ゲストのメッセージは `__wbindgen_throw` の内容をそのまま返し、それ以外は 1 行目だけを返す
+    let message = match err {
+        RenderError::Guest(message) => message,
+        err => err
+            .to_string()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    };
+    (status, kind, message)
+}
+
+/// ヘッダを作る。ヘッダに載せられない値 (ASCII 以外や改行を含む) ならエラー。
+fn header(name: &str, value: &str) -> Result<Header, String> {
+    if value.contains(['\r', '\n']) {
+        return Err(format!("{name} header contains a line break: {value:?}"));
+    }
+    Header::from_bytes(name, value).map_err(|()| format!("invalid {name} header: {value:?}"))
+}
+
+fn content_type(mime_type: &'static str) -> Header {
+    header("Content-Type", mime_type).expect("MIME types are valid header values")
+}
+
+fn json_response(status: u16, body: &serde_json::Value) -> tiny_http::ResponseBox {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(content_type("application/json"))
+        .boxed()
+}
+
+/// クエリ文字列 (`width=512&background=%23fff` など) をレンダリングとエンコードの
+/// オプションに変換する。
+///
+/// パラメータ名は CLI の `render` のフラグと同じ。
+pub fn options_from_query(query: &str) -> Result<(RenderOptions, EncodeOptions), RenderError> {
+    let mut options = RenderOptions::default();
+    let mut encode = EncodeOptions::default();
+    let (mut width, mut height) = (None, None);
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        let invalid = || RenderError::InvalidOptions(format!("invalid value for {key}: {value:?}"));
+        match key {
+            "width" => width = Some(value.parse().map_err(|_| invalid())?),
+            "height" => height = Some(value.parse().map_err(|_| invalid())?),
+            "zoom" => options.zoom = value.parse().map_err(|_| invalid())?,
+            "background" => options.background = Some(value.parse::<Color>()?),
+            "dpi" => options.dpi = value.parse().map_err(|_| invalid())?,
+            "font_size" | "font-size" => {
+                options.font_size = value.parse().map_err(|_| invalid())?
+            }
+            "format" => encode.format = value.parse()?,
+            "compression" => encode.png_compression = Some(value.parse().map_err(|_| invalid())?),
+            "quality" => encode.jpeg_quality = value.parse().map_err(|_| invalid())?,
+            _ => {
+                return Err(RenderError::InvalidOptions(format!(
+                    "unknown parameter {key:?}"
+                )))
+            }
+        }
+    }
+
+    options.fit_to = match (width, height) {
+        (None, None) => FitTo::Original,
+        (Some(w), None) => FitTo::Width(w),
+        (None, Some(h)) => FitTo::Height(h),
+        (Some(w), Some(h)) => FitTo::Size(w, h),
+    };
+    options.validate()?;
+    if let Some(background) = options.background {
+        encode.matte = background;
+    }
+    encode.validate()?;
+    Ok((options, encode))
+}
+
+fn percent_decode(s: &str) -> Result<String, RenderError> {
+    let invalid = || RenderError::InvalidOptions(format!("invalid percent-encoding in {s:?}"));
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).ok_or_else(invalid)?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| invalid())
+}
@@ -0,0 +1,325 @@
+//! ホストとゲストの ABI のテスト。
+//!
+//! `tests/fixtures/stub.wat` は resvg_wasm.wasm と同じエクスポートを持つテスト用のゲストで、
+//! 受け取った引数をそのままピクセルデータとして返せる。`RESVG_WASM` に本物の
+//! resvg_wasm.wasm のパスを設定すると、そのモジュールのシグネチャも確かめる。
+
+use resvg_wasm::abi::Status;
+use resvg_wasm::{abi, describe, Color, FitTo, RenderError, RenderOptions, Renderer};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+fn stub() -> Renderer {
+    Renderer::from_bytes(STUB.as_bytes()).expect("stub guest should load")
+}
+
+/// stub.wat の "echo" が返す arg3..arg10。
+fn echo(pixels: &[u8]) -> [i32; 8] {
+    let mut args = [0; 8];
+    for (arg, bytes) in args.iter_mut().zip(pixels.chunks_exact(4)) {
+        *arg = i32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    args
+}
+
+#[test]
+fn stub_matches_expected_exports() {
+    let renderer = stub();
+    abi::check(renderer.module()).unwrap();
+}
+
+#[test]
+fn real_module_matches_expected_exports() {
+    let Some(path) = std::env::var_os("RESVG_WASM") else {
+        eprintln!("RESVG_WASM is not set; skipping");
+        return;
+    };
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::from_file(&engine, &path).unwrap();
+    if let Err(err) = abi::check(&module) {
+        panic!(
+            "{} does not match the host ABI:\n{err:#}",
+            path.to_string_lossy()
+        );
+    }
+}
+
+#[test]
+fn drifted_context_render_signature_is_reported() {
+    // arg6 (zoom) が f32 になったモジュール
+    let wat = STUB
+        .replace("(param $zoom f64)", "(param $zoom f32)")
+        .replace(
+            "(f64.mul (local.get $zoom) (f64.const 1000))",
+            "(f64.promote_f32 (local.get $zoom))",
+        );
+    let err = Renderer::from_bytes(wat.as_bytes())
+        .err()
+        .expect("drifted signature should be rejected");
+    let message = format!("{err:#}");
+    assert!(
+        message.contains("`context_render` has signature (i32, i32, i32, i32, i32, f32, i32, i32, i32, i32) -> i32"),
+        "{message}"
+    );
+    assert!(message.contains(abi::CONTEXT_RENDER), "{message}");
+}
+
+#[test]
+fn missing_exports_are_all_reported() {
+    let wat = STUB
+        .replace("(export \"context_render\")", "")
+        .replace("(export \"__wbindgen_malloc\")", "");
+    let message = format!("{:#}", Renderer::from_bytes(wat.as_bytes()).err().unwrap());
+    assert!(
+        message.contains("missing export `context_render`"),
+        "{message}"
+    );
+    assert!(
+        message.contains("missing export `__wbindgen_malloc`"),
+        "{message}"
+    );
+}
+
+#[test]
+fn inspect_flags_unresolved_imports_and_unused_exports() {
+    let wat = STUB.replacen(
+        "(module",
+        "(module (import \"env\" \"abort\" (func (param i32)))",
+        1,
+    );
+    let end = wat.rfind(')').unwrap();
+    let wat = format!("{} (func (export \"extra\")))", &wat[..end]);
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::new(&engine, &wat).unwrap();
+    let inspection = abi::inspect(&module);
+
+    let status = |entries: &[abi::Entry], name: &str| {
+        entries
+            .iter()
+            .find(|e| e.qualified_name() == name)
+            .unwrap()
+            .status
+            .clone()
+    };
+    assert_eq!(
+        status(&inspection.imports, "env::abort"),
+        Status::Unresolved
+    );
+    assert_eq!(
+        status(
+            &inspection.imports,
+            "__wbindgen_placeholder__::__wbindgen_throw"
+        ),
+        Status::Ok
+    );
+    assert_eq!(status(&inspection.exports, "extra"), Status::Unused);
+    assert_eq!(status(&inspection.exports, "context_render"), Status::Ok);
+    assert_eq!(
+        status(&inspection.exports, "__wbindgen_realloc"),
+        Status::Absent
+    );
+    assert_eq!(
+        inspection.problems(),
+        ["import `env::abort` ((i32) -> ()) is not provided by the host"]
+    );
+}
+
+#[test]
+fn descriptors_are_decoded_into_rust_signatures() {
+    // fn context_render(&[u8], u32, Option<&str>) -> Result<Vec<u8>, JsValue>
+    let words = [13, 0, 3, 17, 20, 1, 5, 28, 17, 16, 29, 21, 1, 29, 21, 1];
+    let calls: String = words
+        .iter()
+        .map(|w| format!("(call $d (i32.const {w}))"))
+        .collect();
+    let wat = format!(
+        r#"(module
+            (import "__wbindgen_placeholder__" "__wbindgen_describe" (func $d (param i32)))
+            (import "__wbindgen_placeholder__" "__wbg_log" (func (param i32 i32)))
+            (func (export "__wbindgen_describe_context_render") {calls})
+            (func (export "__wbindgen_describe_broken") (call $d (i32.const 999))))"#
+    );
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::new(&engine, &wat).unwrap();
+    let described = describe::read(&module).unwrap();
+
+    assert_eq!(described.len(), 2);
+    assert_eq!(
+        described[0].to_string(),
+        "fn context_render(&[u8], u32, Option<&str>) -> Result<Vec<u8>, JsValue>"
+    );
+    assert_eq!(described[1].words, [999]);
+    assert!(described[1].descriptor.is_err());
+
+    // 処理済みの wasm には記述子がない
+    let stub = wasmtime::Module::new(&engine, STUB).unwrap();
+    assert!(describe::read(&stub).unwrap().is_empty());
+}
+
+#[test]
+fn options_are_passed_in_abi_order() {
+    let mut renderer = stub();
+    let opts = RenderOptions {
+        fit_to: FitTo::Size(320, 240),
+        zoom: 1.5,
+        background: Some(Color::rgba(0x11, 0x22, 0x33, 0x44)),
+        dpi: 72,
+        font_size: 16,
+    };
+    let pixmap = renderer.render_pixmap(b"echo", &opts).unwrap();
+    assert_eq!((pixmap.width, pixmap.height), (8, 1));
+
+    let [context, mode, width, zoom, height, background, dpi, font_size] = echo(&pixmap.data);
+    assert_eq!(context, 0);
+    // 0x100 はピクセルデータを要求するフラグ
+    assert_eq!(mode, 0x100 | 3);
+    assert_eq!((width, height), (320, 240));
+    assert_eq!(zoom, 1500);
+    assert_eq!(background as u32, 0x11223344);
+    assert_eq!((dpi, font_size), (72, 16));
+}
+
+#[test]
+fn fit_modes_map_to_arg4() {
+    let mut renderer = stub();
+    let cases = [
+        (FitTo::Original, 0, 0, 0),
+        (FitTo::Width(10), 1, 10, 0),
+        (FitTo::Height(20), 2, 0, 20),
+        (FitTo::Size(10, 20), 3, 10, 20),
+    ];
+    for (fit_to, expected_mode, expected_width, expected_height) in cases {
+        let opts = RenderOptions {
+            fit_to,
+            ..RenderOptions::default()
+        };
+        let pixmap = renderer.render_pixmap(b"echo", &opts).unwrap();
+        let [_, mode, width, _, height, background, ..] = echo(&pixmap.data);
+        assert_eq!(mode & 0xff, expected_mode, "{fit_to:?}");
+        assert_eq!(
+            (width, height),
+            (expected_width, expected_height),
+            "{fit_to:?}"
+        );
+        // 背景色なしは 0 (透明)
+        assert_eq!(background, 0);
+    }
+}
+
+#[test]
+fn parsed_context_is_passed_as_arg3() {
+    let mut renderer = stub();
+    let mut context = renderer.parse(b"echo", &RenderOptions::default()).unwrap();
+    assert!(context.is_cached());
+    let pixmap = context.render_pixmap(&RenderOptions::default()).unwrap();
+    let [context_id, ..] = echo(&pixmap.data);
+    assert_eq!(context_id, 1);
+}
+
+#[test]
+fn output_descriptor_is_read_and_validated() {
+    let mut renderer = stub();
+    let png = renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    assert_eq!(png.len(), 70);
+
+    let opts = RenderOptions {
+        fit_to: FitTo::Size(3, 2),
+        background: Some(Color::rgba(0xff, 0, 0, 0xff)),
+        ..RenderOptions::default()
+    };
+    let pixmap = renderer.render_pixmap(b"<svg/>", &opts).unwrap();
+    assert_eq!((pixmap.width, pixmap.height), (3, 2));
+    assert!(pixmap
+        .data
+        .chunks_exact(4)
+        .all(|px| px == [0xff, 0, 0, 0xff]));
+}
+
+#[test]
+fn guest_throw_becomes_guest_error_and_instance_recovers() {
+    let mut renderer = stub();
+    match renderer.render(b"throw", &RenderOptions::default()) {
+        Err(RenderError::Guest(message)) => assert_eq!(message, "stub error"),
+        other => panic!("expected a guest error, got {other:?}"),
+    }
+    // 次のレンダリングは作り直したインスタンスで成功する
+    renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+}
+
+#[test]
+fn invalid_options_are_rejected_before_calling_the_guest() {
+    let mut renderer = stub();
+    let opts = RenderOptions {
+        zoom: 0.0,
+        ..RenderOptions::default()
+    };
+    assert!(matches!(
+        renderer.render(b"<svg/>", &opts),
+        Err(RenderError::InvalidOptions(_))
+    ));
+}
+
+#[test]
+fn guest_trap_names_the_export() {
+    let wat = STUB.replace(
+        "(then (call $throw (i32.const 256) (i32.const 10)))",
+        "(then unreachable)",
+    );
+    let mut renderer = Renderer::from_bytes(wat.as_bytes()).unwrap();
+    match renderer.render(b"throw", &RenderOptions::default()) {
+        Err(RenderError::Trap { export, .. }) => assert_eq!(export, "context_render"),
+        other => panic!("expected a trap, got {other:?}"),
+    }
+}
+
+#[test]
+fn out_of_bounds_output_reports_offsets() {
+    // PNG の長さを線形メモリより大きくする
+    let wat = STUB.replace(
+        "(i32.store (i32.const 516) (i32.const 70))",
+        "(i32.store (i32.const 516) (i32.const 0x7fffffff))",
+    );
+    let mut renderer = Renderer::from_bytes(wat.as_bytes()).unwrap();
+    match renderer.render(b"<svg/>", &RenderOptions::default()) {
+        Err(RenderError::OutOfBounds {
+            what,
+            ptr,
+            len,
+            memory,
+        }) => {
+            assert_eq!(what, "output buffer");
+            assert_eq!((ptr, len), (1024, 0x7fffffff));
+            assert!(memory >= 65536);
+        }
+        other => panic!("expected an out-of-bounds error, got {other:?}"),
+    }
+}
+
+#[test]
+fn missing_memory_is_an_abi_error() {
+    let wat = STUB.replace("(memory (export \"memory\") 1)", "(memory 1)");
+    match Renderer::from_bytes(wat.as_bytes()) {
+        Err(RenderError::Abi(problems)) => assert_eq!(problems, ["missing export `memory`"]),
+        Err(other) => panic!("expected an ABI error, got {other:?}"),
+        Ok(_) => panic!("a module without memory should be rejected"),
+    }
+}
+
+#[test]
+fn load_errors_carry_the_path() {
+    let path = std::path::Path::new("does/not/exist.wasm");
+    match Renderer::from_file(path) {
+        Err(err @ RenderError::Load { .. }) => {
+            assert!(err
+                .to_string()
+                .starts_with("failed to load does/not/exist.wasm: "));
+        }
+        Err(other) => panic!("expected a load error, got {other:?}"),
+        Ok(_) => panic!("a missing file should not load"),
+    }
+}
@@ -0,0 +1,104 @@
+//! バッチの入力の集め方と出力パスの割り当てのテスト。
+
+use std::path::{Path, PathBuf};
+
+use resvg_wasm::batch::{self, BatchItem};
+use resvg_wasm::{ImageFormat, RenderError};
+
+/// テストごとの一時ディレクトリ。drop で消す。
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str, files: &[&str]) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("resvg-wasm-batch-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "<svg/>").unwrap();
+        }
+        TempDir(dir)
+    }
+
+    fn arg(&self, path: &str) -> String {
+        self.0.join(path).display().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn outputs(items: &[BatchItem], out: &Path) -> Vec<PathBuf> {
+    items
+        .iter()
+        .map(|item| item.output.strip_prefix(out).unwrap().to_path_buf())
+        .collect()
+}
+
+#[test]
+fn same_named_glob_matches_keep_their_directories() {
+    let dir = TempDir::new("glob", &["in/a/icon.svg", "in/b/icon.svg", "in/c.svg"]);
+    let out = dir.0.join("out");
+
+    let items = batch::plan(&[dir.arg("in/*/icon.svg")], &out, ImageFormat::Png).unwrap();
+    assert_eq!(
+        outputs(&items, &out),
+        [PathBuf::from("a/icon.png"), PathBuf::from("b/icon.png")]
+    );
+
+    let items = batch::plan(&[dir.arg("in/**/*.svg")], &out, ImageFormat::Png).unwrap();
+    assert_eq!(
+        outputs(&items, &out),
+        [
+            PathBuf::from("a/icon.png"),
+            PathBuf::from("b/icon.png"),
+            PathBuf::from("c.png")
+        ]
+    );
+
+    // ワイルドカードのないパターンはファイル名だけ
+    let items = batch::plan(&[dir.arg("in/a/icon.svg")], &out, ImageFormat::Jpeg).unwrap();
+    assert_eq!(outputs(&items, &out), [PathBuf::from("icon.jpg")]);
+}
+
+#[test]
+fn colliding_outputs_are_rejected_before_rendering() {
+    let dir = TempDir::new("collide", &["a/icon.svg", "b/icon.svg"]);
+    let out = dir.0.join("out");
+
+    // 別々のディレクトリを入力にすると、どちらも out/icon.png になる
+    match batch::plan(&[dir.arg("a"), dir.arg("b")], &out, ImageFormat::Png) {
+        Err(RenderError::InvalidOptions(message)) => {
+            assert!(message.contains("would both be written to"), "{message}");
+            assert!(message.contains("icon.png"), "{message}");
+        }
+        other => panic!("expected an invalid-options error, got {other:?}"),
+    }
+    assert!(!out.exists());
+
+    // 同じ入力が重なるだけなら 1 件にまとめる
+    let items = batch::plan(&[dir.arg("a"), dir.arg("a/*.svg")], &out, ImageFormat::Png).unwrap();
+    assert_eq!(items.len(), 1);
+
+    // 出力先を持たない find_inputs は重なっても構わない
+    let inputs = batch::find_inputs(&[dir.arg("a"), dir.arg("b")]).unwrap();
+    assert_eq!(inputs.len(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_directories_are_not_followed() {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new("symlink", &["in/sub/a.svg"]);
+    // 自分自身を指すループ
+    symlink(dir.0.join("in"), dir.0.join("in/sub/loop")).unwrap();
+    let out = dir.0.join("out");
+
+    let items = batch::plan(&[dir.arg("in")], &out, ImageFormat::Png).unwrap();
+    assert_eq!(outputs(&items, &out), [PathBuf::from("sub/a.png")]);
+}
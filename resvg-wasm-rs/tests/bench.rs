@@ -0,0 +1,132 @@
+//! bench の計測と集計のテスト。
+
+use std::time::Duration;
+
+use resvg_wasm::bench::{self, BenchInput, BenchOptions, Summary};
+use resvg_wasm::{EngineOptions, RenderOptions, Renderer, RendererConfig};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+fn input(path: &str, svg: &[u8]) -> BenchInput {
+    BenchInput {
+        path: path.into(),
+        svg: svg.to_vec(),
+    }
+}
+
+#[test]
+fn summary_uses_nearest_rank_percentiles() {
+    let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+    let summary = Summary::of(&samples);
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p95, Duration::from_millis(95));
+    assert_eq!(summary.max, Duration::from_millis(100));
+    assert_eq!(summary.total, Duration::from_millis(5050));
+
+    let single = Summary::of(&[Duration::from_millis(7)]);
+    assert_eq!(
+        (single.p50, single.p95),
+        (Duration::from_millis(7), Duration::from_millis(7))
+    );
+    assert_eq!(Summary::of(&[]), Summary::default());
+}
+
+#[test]
+fn renderer_records_the_phases_of_the_last_render() {
+    let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+    assert_eq!(renderer.last_timings(), Default::default());
+    renderer
+        .render(&vec![b' '; 1 << 20], &RenderOptions::default())
+        .unwrap();
+    let timings = renderer.last_timings();
+    assert!(timings.upload > Duration::ZERO, "{timings:?}");
+    assert!(timings.call > Duration::ZERO, "{timings:?}");
+    assert!(timings.copy > Duration::ZERO, "{timings:?}");
+
+    // パース済みのコンテキストは SVG をアップロードしない
+    let mut context = renderer
+        .parse(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+    context.render(&RenderOptions::default()).unwrap();
+    drop(context);
+    assert_eq!(renderer.last_timings().upload, Duration::ZERO);
+}
+
+#[test]
+fn bench_reports_every_input() {
+    let inputs = [
+        input("a.svg", b"<svg/>"),
+        input("b.svg", b"<svg width=\"4\"/>"),
+    ];
+    let options = BenchOptions {
+        iterations: 3,
+        compile_iterations: 2,
+    };
+    let report = bench::run(
+        STUB.as_bytes(),
+        &RendererConfig::default(),
+        &inputs,
+        &RenderOptions::default(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.compile.count, 2);
+    assert_eq!(report.instantiate.count, 3);
+    assert_eq!(report.renders(), 6);
+    assert!(report.throughput() > 0.0);
+
+    let paths: Vec<_> = report.inputs.iter().map(|i| i.path.clone()).collect();
+    assert_eq!(paths, ["a.svg", "b.svg"].map(std::path::PathBuf::from));
+    for input in &report.inputs {
+        assert_eq!(input.call.count, 3);
+        assert!(input.call.p50 <= input.call.p95 && input.call.p95 <= input.call.max);
+        assert!(input.total.total >= input.call.total);
+    }
+
+    let json = report.to_json();
+    assert_eq!(json["renders"], 6);
+    assert_eq!(json["inputs"][1]["path"], "b.svg");
+    assert_eq!(json["inputs"][0]["upload"]["count"], 3);
+}
+
+#[test]
+fn failed_inputs_do_not_stop_the_bench() {
+    let inputs = [input("throw.svg", b"throw"), input("ok.svg", b"<svg/>")];
+    let report = bench::run(
+        STUB.as_bytes(),
+        &RendererConfig::default(),
+        &inputs,
+        &RenderOptions::default(),
+        &BenchOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(report.inputs.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].0, std::path::PathBuf::from("throw.svg"));
+}
+
+#[test]
+fn bench_fits_in_a_single_pooled_instance() {
+    let config = RendererConfig {
+        engine: EngineOptions {
+            pooling: Some(1),
+            ..EngineOptions::default()
+        },
+        ..RendererConfig::default()
+    };
+    let report = bench::run(
+        STUB.as_bytes(),
+        &config,
+        &[input("a.svg", b"<svg/>")],
+        &RenderOptions::default(),
+        &BenchOptions {
+            iterations: 2,
+            compile_iterations: 1,
+        },
+    )
+    .unwrap();
+    assert_eq!(report.instantiate.count, 2);
+    assert_eq!(report.renders(), 2);
+    assert!(report.failures.is_empty(), "{:?}", report.failures);
+}
@@ -0,0 +1,210 @@
+//! wasm-bindgen の組み込み関数のホスト側実装のテスト。
+
+use resvg_wasm::abi::{self, Status};
+use resvg_wasm::{HostState, Renderer};
+use wasmtime::{ExternRef, Instance, Ref, Store, Table};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// externref テーブルをエクスポートし、それを操作する組み込み関数を呼ぶ関数を足した stub。
+fn externref_guest(table_export: &str) -> String {
+    let wat = STUB.replacen(
+        "(module",
+        r#"(module
+  (import "__wbindgen_placeholder__" "__wbindgen_init_externref_table" (func $init))
+  (import "__wbindgen_placeholder__" "__wbindgen_externref_table_grow" (func $grow (param i32) (result i32)))
+  (import "__wbindgen_placeholder__" "__wbindgen_externref_table_set_null" (func $set_null (param i32)))"#,
+        1,
+    );
+    let end = wat.rfind(')').unwrap();
+    format!(
+        r#"{}
+  (table (export "{table_export}") 1 externref)
+  (func (export "init") (call $init))
+  (func (export "grow") (param i32) (result i32) (call $grow (local.get 0)))
+  (func (export "set_null") (param i32) (call $set_null (local.get 0))))"#,
+        &wat[..end]
+    )
+}
+
+fn instantiate(renderer: &Renderer) -> (Store<HostState>, Instance) {
+    let mut store = Store::new(renderer.engine(), HostState::default());
+    let instance = renderer
+        .linker()
+        .instantiate(&mut store, renderer.module())
+        .unwrap();
+    (store, instance)
+}
+
+fn table(store: &mut Store<HostState>, instance: &Instance) -> Table {
+    instance
+        .get_table(&mut *store, "__wbindgen_export_2")
+        .unwrap()
+}
+
+fn is_null(store: &mut Store<HostState>, table: &Table, index: u64) -> bool {
+    table
+        .get(&mut *store, index)
+        .unwrap()
+        .unwrap_extern()
+        .is_none()
+}
+
+#[test]
+fn externref_intrinsics_grow_and_clear_the_exported_table() {
+    let renderer = Renderer::from_bytes(externref_guest("__wbindgen_export_2").as_bytes()).unwrap();
+    let (mut store, instance) = instantiate(&renderer);
+    let table = table(&mut store, &instance);
+
+    instance
+        .get_typed_func::<(), ()>(&mut store, "init")
+        .unwrap()
+        .call(&mut store, ())
+        .unwrap();
+    assert_eq!(table.size(&store), 5);
+
+    let grow = instance
+        .get_typed_func::<i32, i32>(&mut store, "grow")
+        .unwrap();
+    assert_eq!(grow.call(&mut store, 3).unwrap(), 5);
+    assert_eq!(table.size(&store), 8);
+    assert!((0..8).all(|i| is_null(&mut store, &table, i)));
+
+    let value = ExternRef::new(&mut store, 42u32).unwrap();
+    table.set(&mut store, 6, Ref::Extern(Some(value))).unwrap();
+    assert!(!is_null(&mut store, &table, 6));
+    let set_null = instance
+        .get_typed_func::<i32, ()>(&mut store, "set_null")
+        .unwrap();
+    set_null.call(&mut store, 6).unwrap();
+    assert!(is_null(&mut store, &table, 6));
+
+    // テーブルの外はトラップになる
+    assert!(set_null.call(&mut store, 8).is_err());
+}
+
+#[test]
+fn externref_table_grow_reports_failure_as_minus_one() {
+    let wat = externref_guest("__wbindgen_export_2").replace(
+        "(table (export \"__wbindgen_export_2\") 1 externref)",
+        "(table (export \"__wbindgen_export_2\") 1 2 externref)",
+    );
+    let renderer = Renderer::from_bytes(wat.as_bytes()).unwrap();
+    let (mut store, instance) = instantiate(&renderer);
+    let grow = instance
+        .get_typed_func::<i32, i32>(&mut store, "grow")
+        .unwrap();
+    assert_eq!(grow.call(&mut store, 1).unwrap(), 1);
+    assert_eq!(grow.call(&mut store, 1).unwrap(), -1);
+    assert_eq!(table(&mut store, &instance).size(&store), 2);
+}
+
+#[test]
+fn externref_intrinsics_without_a_table_are_stubs() {
+    let status = |wat: &str, name: &str| {
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(&engine, wat).unwrap();
+        abi::inspect(&module)
+            .imports
+            .into_iter()
+            .find(|e| e.name == name)
+            .unwrap()
+            .status
+    };
+
+    let with_table = externref_guest("__wbindgen_export_2");
+    let without_table = externref_guest("not_bindgen");
+    for name in [
+        "__wbindgen_init_externref_table",
+        "__wbindgen_externref_table_grow",
+        "__wbindgen_externref_table_set_null",
+    ] {
+        assert_eq!(status(&with_table, name), Status::Ok, "{name}");
+        assert_eq!(status(&without_table, name), Status::Stub, "{name}");
+    }
+
+    // スタブは呼ばれた時点でエラーになる
+    let renderer = Renderer::from_bytes(without_table.as_bytes()).unwrap();
+    let (mut store, instance) = instantiate(&renderer);
+    let err = instance
+        .get_typed_func::<(), ()>(&mut store, "init")
+        .unwrap()
+        .call(&mut store, ())
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("unsupported wasm-bindgen import"),
+        "{err:#}"
+    );
+}
+
+/// 長さ `len` の文字列を組み込み関数に渡す関数を足した stub。
+fn string_guest() -> String {
+    let wat = STUB.replacen(
+        "(module",
+        r#"(module
+  (import "__wbindgen_placeholder__" "__wbindgen_string_new" (func $string_new (param i32 i32) (result i32)))
+  (import "__wbindgen_placeholder__" "__wbindgen_string_get" (func $string_get (param i32 i32)))"#,
+        1,
+    );
+    let end = wat.rfind(')').unwrap();
+    format!(
+        r#"{}
+  (func $fill (param $len i32) (result i32)
+    (local $p i32)
+    (local.set $p (call $alloc (local.get $len)))
+    (memory.fill (local.get $p) (i32.const 0x61) (local.get $len))
+    (local.get $p))
+  ;; string_new で作った文字列を string_get で読み戻し、その長さを返す
+  (func (export "round_trip") (param $len i32) (result i32)
+    (call $string_get (i32.const 512) (call $string_new (call $fill (local.get $len)) (local.get $len)))
+    (i32.load offset=4 (i32.const 512)))
+  (func (export "string_new") (param i32 i32) (result i32)
+    (call $string_new (local.get 0) (local.get 1)))
+  (func (export "throw_long") (param $len i32)
+    (call $throw (call $fill (local.get $len)) (local.get $len))))"#,
+        &wat[..end]
+    )
+}
+
+#[test]
+fn strings_are_passed_without_truncation() {
+    let renderer = Renderer::from_bytes(string_guest().as_bytes()).unwrap();
+    let (mut store, instance) = instantiate(&renderer);
+    let round_trip = instance
+        .get_typed_func::<i32, i32>(&mut store, "round_trip")
+        .unwrap();
+    assert_eq!(round_trip.call(&mut store, 5).unwrap(), 5);
+    assert_eq!(round_trip.call(&mut store, 200_000).unwrap(), 200_000);
+
+    // メモリの外を指す文字列はトラップになる
+    let string_new = instance
+        .get_typed_func::<(i32, i32), i32>(&mut store, "string_new")
+        .unwrap();
+    let size = instance
+        .get_memory(&mut store, "memory")
+        .unwrap()
+        .data_size(&store) as i32;
+    let err = string_new.call(&mut store, (size - 4, 8)).unwrap_err();
+    assert!(
+        err.root_cause().to_string().contains("out of bounds"),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn thrown_messages_are_truncated() {
+    let renderer = Renderer::from_bytes(string_guest().as_bytes()).unwrap();
+    let (mut store, instance) = instantiate(&renderer);
+    let throw_long = instance
+        .get_typed_func::<i32, ()>(&mut store, "throw_long")
+        .unwrap();
+
+    let message = |store: &mut Store<HostState>, len| {
+        let err = throw_long.call(&mut *store, len).unwrap_err();
+        err.root_cause().to_string()
+    };
+    assert_eq!(message(&mut store, 10), "a".repeat(10));
+    let long = message(&mut store, 200_000);
+    assert_eq!(long.len(), 64 * 1024 + 3);
+    assert!(long.ends_with("a..."));
+}
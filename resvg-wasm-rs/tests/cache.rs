@@ -0,0 +1,143 @@
+//! コンパイル済みモジュールのキャッシュのテスト。
+
+use std::path::PathBuf;
+
+use resvg_wasm::cache::{self, ModuleCache};
+use resvg_wasm::{EngineOptions, OptLevel, RenderOptions, Renderer, RendererConfig};
+use wasmtime::{Engine, Module};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// テストごとの空のキャッシュディレクトリ。drop で消す。
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("resvg-wasm-cache-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn engine(opt_level: OptLevel) -> Engine {
+    RendererConfig {
+        engine: EngineOptions {
+            opt_level,
+            ..EngineOptions::default()
+        },
+        ..RendererConfig::default()
+    }
+    .engine()
+    .unwrap()
+}
+
+fn engine_without_simd() -> Engine {
+    RendererConfig {
+        engine: EngineOptions {
+            simd: false,
+            relaxed_simd: false,
+            ..EngineOptions::default()
+        },
+        ..RendererConfig::default()
+    }
+    .engine()
+    .unwrap()
+}
+
+/// `module` がレンダリングに使えるか。
+fn renders(engine: &Engine, module: Module) {
+    let mut renderer = Renderer::new(engine.clone(), module).unwrap();
+    renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+}
+
+#[test]
+fn key_depends_on_the_wasm_bytes() {
+    let cache = ModuleCache::new("cache");
+    let engine = engine(OptLevel::Speed);
+    let changed = STUB.replace("stub error", "stub fault");
+
+    let path = cache.path_for(&engine, STUB.as_bytes());
+    assert_eq!(path, cache.path_for(&engine, STUB.as_bytes()));
+    assert_ne!(path, cache.path_for(&engine, changed.as_bytes()));
+    assert_eq!(path.parent(), Some(cache.dir()));
+    assert_eq!(path.extension().unwrap(), "cwasm");
+}
+
+#[test]
+fn key_depends_on_the_engine_config() {
+    let cache = ModuleCache::new("cache");
+    let wasm = STUB.as_bytes();
+    let speed = cache.path_for(&engine(OptLevel::Speed), wasm);
+
+    // 同じ設定なら Engine を作り直しても同じキー
+    assert_eq!(speed, cache.path_for(&engine(OptLevel::Speed), wasm));
+    assert_ne!(speed, cache.path_for(&engine(OptLevel::None), wasm));
+    assert_ne!(speed, cache.path_for(&engine_without_simd(), wasm));
+}
+
+#[test]
+fn compiled_module_is_stored_and_reused() {
+    let dir = TempDir::new("reuse");
+    let cache = ModuleCache::new(&dir.0);
+    let engine = engine(OptLevel::Speed);
+    let path = cache.path_for(&engine, STUB.as_bytes());
+
+    renders(&engine, cache.load(&engine, STUB.as_bytes()).unwrap());
+    let stored = std::fs::read(&path).unwrap();
+    assert!(engine.detect_precompiled(&stored).is_some());
+
+    renders(&engine, cache.load(&engine, STUB.as_bytes()).unwrap());
+    assert_eq!(std::fs::read(&path).unwrap(), stored);
+}
+
+#[test]
+fn corrupted_entry_falls_back_to_compiling() {
+    let dir = TempDir::new("corrupted");
+    let cache = ModuleCache::new(&dir.0);
+    let engine = engine(OptLevel::Speed);
+    let path = cache.path_for(&engine, STUB.as_bytes());
+
+    std::fs::create_dir_all(&dir.0).unwrap();
+    std::fs::write(&path, b"not a compiled module").unwrap();
+    renders(&engine, cache.load(&engine, STUB.as_bytes()).unwrap());
+    // 壊れたエントリはコンパイルし直した結果で置き換わる
+    let stored = std::fs::read(&path).unwrap();
+    assert!(engine.detect_precompiled(&stored).is_some());
+}
+
+#[test]
+fn incompatible_entry_falls_back_to_compiling() {
+    let dir = TempDir::new("incompatible");
+    let cache = ModuleCache::new(&dir.0);
+    let engine = engine(OptLevel::Speed);
+    let path = cache.path_for(&engine, STUB.as_bytes());
+
+    // 別の設定の Engine でコンパイルした結果を、このキーの場所に置く
+    let other = engine_without_simd();
+    let foreign = other.precompile_module(STUB.as_bytes()).unwrap();
+    std::fs::create_dir_all(&dir.0).unwrap();
+    std::fs::write(&path, &foreign).unwrap();
+    // SAFETY: 直前に別の Engine で precompile_module した結果 (互換性の検査で弾かれる)
+    assert!(unsafe { Module::deserialize(&engine, &foreign) }.is_err());
+
+    renders(&engine, cache.load(&engine, STUB.as_bytes()).unwrap());
+    assert_ne!(std::fs::read(&path).unwrap(), foreign);
+
+    // load_module からでも同じ
+    let wasm = dir.0.join("stub.wat");
+    std::fs::write(&wasm, STUB).unwrap();
+    std::fs::write(&path, &foreign).unwrap();
+    renders(
+        &engine,
+        cache::load_module(&engine, &wasm, Some(&cache)).unwrap(),
+    );
+}
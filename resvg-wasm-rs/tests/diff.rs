@@ -0,0 +1,126 @@
+//! レンダリング結果と基準 PNG の比較のテスト。
+
+use image::{ImageFormat, RgbaImage};
+use resvg_wasm::diff::{self, DiffOptions};
+use resvg_wasm::RenderError;
+
+/// 非乗算の RGBA8 のピクセル列を PNG にする。
+fn png(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+    let image = RgbaImage::from_raw(width, height, pixels.concat()).unwrap();
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png).unwrap();
+    png.into_inner()
+}
+
+fn opts(threshold: f64, max_diff_pixels: usize) -> DiffOptions {
+    DiffOptions {
+        threshold,
+        max_diff_pixels,
+    }
+}
+
+const REFERENCE: [[u8; 4]; 4] = [
+    [0, 0, 0, 255],
+    [100, 100, 100, 255],
+    [255, 255, 255, 255],
+    [10, 20, 30, 255],
+];
+
+#[test]
+fn identical_images_pass() {
+    let reference = png(2, 2, &REFERENCE);
+    let report = diff::compare(&reference, &reference, &DiffOptions::default()).unwrap();
+    assert!(report.passed());
+    assert_eq!((report.width, report.height), (2, 2));
+    assert_eq!(report.reference_size, (2, 2));
+    assert_eq!(report.mismatched, 0);
+    assert_eq!(report.max_delta, 0.0);
+    assert_eq!(report.mismatch_ratio(), 0.0);
+
+    // 差分画像は一致したピクセルを薄いグレーで描く
+    let image = diff::decode_png(&report.diff_image.unwrap().data).unwrap();
+    assert_eq!(image.dimensions(), (2, 2));
+    assert!(image.pixels().all(|p| p.0[0] == p.0[1] && p.0[3] == 255));
+}
+
+#[test]
+fn threshold_and_max_diff_pixels_decide_the_result() {
+    let reference = png(2, 2, &REFERENCE);
+    // 1 ピクセル目が 51/255 = 0.2、2 ピクセル目が 10/255 ずれている
+    let mut pixels = REFERENCE;
+    pixels[0] = [51, 0, 0, 255];
+    pixels[1] = [110, 100, 100, 255];
+    let actual = png(2, 2, &pixels);
+
+    let report = diff::compare(&actual, &reference, &opts(0.1, 0)).unwrap();
+    assert_eq!(report.mismatched, 1);
+    assert!((report.max_delta - 0.2).abs() < 1e-9);
+    assert_eq!(report.mismatch_ratio(), 0.25);
+    assert!(!report.passed());
+    let image = diff::decode_png(&report.diff_image.unwrap().data).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_ne!(image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+
+    // 不一致の数が max_diff_pixels 以内なら合格
+    assert!(diff::compare(&actual, &reference, &opts(0.1, 1))
+        .unwrap()
+        .passed());
+    // 許容差を広げれば一致、0 にすれば 2 ピクセルとも不一致
+    assert_eq!(
+        diff::compare(&actual, &reference, &opts(0.2, 0))
+            .unwrap()
+            .mismatched,
+        0
+    );
+    assert_eq!(
+        diff::compare(&actual, &reference, &opts(0.0, 0))
+            .unwrap()
+            .mismatched,
+        2
+    );
+}
+
+#[test]
+fn fully_transparent_pixels_match_regardless_of_color() {
+    let reference = png(2, 1, &[[0, 0, 0, 0], [255, 0, 0, 128]]);
+    let actual = png(2, 1, &[[255, 255, 255, 0], [255, 0, 0, 128]]);
+    let report = diff::compare(&actual, &reference, &opts(0.0, 0)).unwrap();
+    assert_eq!(report.mismatched, 0);
+    assert!(report.passed());
+
+    // 透明度が違えば、色が同じでも不一致
+    let actual = png(2, 1, &[[0, 0, 0, 0], [255, 0, 0, 255]]);
+    let report = diff::compare(&actual, &reference, &opts(0.1, 0)).unwrap();
+    assert_eq!(report.mismatched, 1);
+}
+
+#[test]
+fn size_mismatch_fails_without_a_diff_image() {
+    let reference = png(2, 2, &REFERENCE);
+    let actual = png(4, 1, &REFERENCE);
+    let report = diff::compare(&actual, &reference, &opts(1.0, usize::MAX)).unwrap();
+    assert!(!report.passed());
+    assert_eq!((report.width, report.height), (4, 1));
+    assert_eq!(report.reference_size, (2, 2));
+    assert_eq!(report.mismatched, 4);
+    assert_eq!(report.max_delta, 1.0);
+    assert!(report.diff_image.is_none());
+}
+
+#[test]
+fn invalid_inputs_are_errors() {
+    let reference = png(2, 2, &REFERENCE);
+    assert!(matches!(
+        diff::compare(&reference, &reference, &opts(1.5, 0)),
+        Err(RenderError::InvalidOptions(_))
+    ));
+    // 壊れた基準画像は設定の誤り、壊れたレンダリング結果は出力の誤り
+    assert!(matches!(
+        diff::compare(&reference, b"not a png", &DiffOptions::default()),
+        Err(RenderError::InvalidOptions(_))
+    ));
+    assert!(matches!(
+        diff::compare(b"not a png", &reference, &DiffOptions::default()),
+        Err(RenderError::InvalidOutput(_))
+    ));
+}
@@ -0,0 +1,102 @@
+//! ホスト側のエンコードのテスト。
+
+use resvg_wasm::serve::options_from_query;
+use resvg_wasm::{encode, Color, EncodeOptions, ImageFormat, Pixmap, RenderError};
+
+/// 透明、不透明、半透明 (乗算済み) の 4 ピクセル。
+fn pixmap() -> Pixmap {
+    Pixmap::new(
+        4,
+        1,
+        vec![
+            0, 0, 0, 0, // 透明
+            10, 20, 30, 255, // 不透明
+            64, 32, 0, 128, // 半透明の (128, 64, 0)
+            200, 0, 0, 100, // アルファより大きい不正な値
+        ],
+    )
+    .unwrap()
+}
+
+#[test]
+fn premultiplied_alpha_is_undone() {
+    assert_eq!(
+        pixmap().to_straight_rgba(),
+        [
+            0, 0, 0, 0, //
+            10, 20, 30, 255, //
+            128, 64, 0, 128, //
+            255, 0, 0, 100,
+        ]
+    );
+}
+
+#[test]
+fn flatten_composites_over_the_matte() {
+    let red = Color::rgba(0xff, 0, 0, 0xff);
+    assert_eq!(
+        pixmap().flatten(red),
+        [
+            255, 0, 0, // 透明な部分は matte の色
+            10, 20, 30, // 不透明な部分はそのまま
+            191, 32, 0, //
+            255, 0, 0,
+        ]
+    );
+
+    let white = Color::rgba(0xff, 0xff, 0xff, 0xff);
+    assert_eq!(&pixmap().flatten(white)[6..9], [191, 159, 127]);
+}
+
+#[test]
+fn png_stores_straight_alpha() {
+    let opts = EncodeOptions {
+        png_compression: Some(6),
+        ..EncodeOptions::default()
+    };
+    let image = encode(&pixmap(), &opts).unwrap();
+    assert_eq!(
+        (image.format, image.width, image.height),
+        (ImageFormat::Png, 4, 1)
+    );
+    let decoded = image::load_from_memory(&image.data).unwrap().into_rgba8();
+    assert_eq!(decoded.into_raw(), pixmap().to_straight_rgba());
+}
+
+#[test]
+fn background_is_the_matte_for_formats_without_alpha() {
+    // CLI の --background と同じく、背景色は matte にもなる
+    let (_, opts) = options_from_query("format=ppm&background=%2300ff00").unwrap();
+    assert_eq!(opts.matte, Color::rgba(0, 0xff, 0, 0xff));
+    let image = encode(&pixmap(), &opts).unwrap();
+    let rgb = image::load_from_memory(&image.data).unwrap().into_rgb8();
+    assert_eq!(rgb.into_raw(), pixmap().flatten(opts.matte));
+    assert_eq!(&pixmap().flatten(opts.matte)[..3], [0, 255, 0]);
+
+    // 指定がなければ白
+    let (_, opts) = options_from_query("format=ppm").unwrap();
+    let image = encode(&pixmap(), &opts).unwrap();
+    let rgb = image::load_from_memory(&image.data).unwrap().into_rgb8();
+    assert_eq!(&rgb.into_raw()[..6], [255, 255, 255, 10, 20, 30]);
+}
+
+#[test]
+fn rgba_is_the_guest_output_as_is() {
+    let opts = EncodeOptions {
+        format: ImageFormat::Rgba,
+        ..EncodeOptions::default()
+    };
+    assert_eq!(encode(&pixmap(), &opts).unwrap().data, pixmap().data);
+}
+
+#[test]
+fn pixmap_size_must_match_the_data() {
+    assert!(matches!(
+        Pixmap::new(2, 2, vec![0; 12]),
+        Err(RenderError::InvalidOutput(_))
+    ));
+    assert!(matches!(
+        Pixmap::new(0, 1, Vec::new()),
+        Err(RenderError::InvalidOutput(_))
+    ));
+}
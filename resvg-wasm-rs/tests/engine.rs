@@ -0,0 +1,345 @@
+//! wasmtime の `Engine` の設定と、レンダリングの資源の上限のテスト。
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use resvg_wasm::batch;
+use resvg_wasm::pool::RenderPool;
+use resvg_wasm::{
+    EncodeOptions, EngineOptions, FitTo, ImageFormat, OptLevel, RenderError, RenderOptions,
+    Renderer, RendererConfig, ResourceLimits,
+};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+fn config(engine: EngineOptions) -> RendererConfig {
+    RendererConfig {
+        engine,
+        ..RendererConfig::default()
+    }
+}
+
+/// SVG が "throw" のときに無限ループする stub。
+fn spinning() -> String {
+    STUB.replace(
+        "(then (call $throw (i32.const 256) (i32.const 10)))",
+        "(then (loop $spin (br $spin)))",
+    )
+}
+
+fn limited(wat: &str, limits: ResourceLimits) -> Renderer {
+    let config = RendererConfig {
+        limits,
+        ..RendererConfig::default()
+    };
+    Renderer::from_bytes_with(wat.as_bytes(), &config).unwrap()
+}
+
+#[test]
+fn every_variant_renders_the_same_output() {
+    let base = EngineOptions::default();
+    let expected = Renderer::from_bytes(STUB.as_bytes())
+        .unwrap()
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+    for (name, engine) in base.variants() {
+        let mut renderer = Renderer::from_bytes_with(STUB.as_bytes(), &config(engine)).unwrap();
+        let png = renderer
+            .render(b"<svg/>", &RenderOptions::default())
+            .unwrap();
+        assert_eq!(png, expected, "{name}");
+    }
+}
+
+#[test]
+fn variants_change_one_option_each() {
+    let names: Vec<String> = EngineOptions::default()
+        .variants()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "opt-level=none",
+            "opt-level=speed-and-size",
+            "pooling=16",
+            "simd=off",
+            "relaxed-simd=off",
+            "memory-cow=off",
+            "parallel-compilation=off",
+        ]
+    );
+
+    let tuned = EngineOptions {
+        opt_level: OptLevel::None,
+        pooling: Some(4),
+        simd: false,
+        relaxed_simd: false,
+        ..EngineOptions::default()
+    };
+    let names: Vec<String> = tuned.variants().into_iter().map(|(name, _)| name).collect();
+    assert!(names.contains(&"opt-level=speed".to_string()), "{names:?}");
+    assert!(names.contains(&"pooling=off".to_string()), "{names:?}");
+    assert!(names.contains(&"simd=on".to_string()), "{names:?}");
+    assert!(!names.iter().any(|name| name.starts_with("relaxed-simd")));
+    for (name, engine) in tuned.variants() {
+        engine.validate().unwrap_or_else(|e| panic!("{name}: {e}"));
+    }
+}
+
+#[test]
+fn relaxed_simd_without_simd_is_rejected() {
+    let engine = EngineOptions {
+        simd: false,
+        ..EngineOptions::default()
+    };
+    let Err(err) = config(engine).engine() else {
+        panic!("engine was created");
+    };
+    assert!(matches!(err, RenderError::InvalidOptions(_)), "{err:?}");
+}
+
+#[test]
+fn pooling_limits_live_instances() {
+    let engine = EngineOptions {
+        pooling: Some(2),
+        ..EngineOptions::default()
+    };
+    let renderer = Renderer::from_bytes_with(STUB.as_bytes(), &config(engine)).unwrap();
+    let mut forked = renderer.fork().unwrap();
+    forked.render(b"<svg/>", &RenderOptions::default()).unwrap();
+
+    // 3 つ目のインスタンスはプールに空きがない
+    assert!(renderer.fork().is_err());
+    drop(forked);
+    renderer.fork().unwrap();
+}
+
+#[test]
+fn pooling_fits_as_many_jobs_as_instances() {
+    let dir = std::env::temp_dir().join(format!("resvg-wasm-pooling-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("in")).unwrap();
+    for i in 0..4 {
+        std::fs::write(dir.join(format!("in/{i}.svg")), "<svg/>").unwrap();
+    }
+    let items = batch::plan(
+        &[dir.join("in").display().to_string()],
+        &dir.join("out"),
+        ImageFormat::Png,
+    )
+    .unwrap();
+
+    let jobs = 2;
+    let engine = EngineOptions {
+        pooling: Some(jobs),
+        ..EngineOptions::default()
+    };
+    let renderer = Renderer::from_bytes_with(STUB.as_bytes(), &config(engine)).unwrap();
+    let mut pool = RenderPool::new(renderer, NonZeroUsize::new(jobs as usize).unwrap());
+    let report = pool
+        .render_batch(
+            &items,
+            &RenderOptions::default(),
+            &EncodeOptions::default(),
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!((report.succeeded(), report.failed()), (4, 0), "{report}");
+
+    // ワーカーが終われば、同じプールでもう一度回せる
+    let report = pool
+        .render_batch(
+            &items,
+            &RenderOptions::default(),
+            &EncodeOptions::default(),
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(report.succeeded(), 4);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fuel_exhaustion_is_a_timeout() {
+    let mut renderer = limited(
+        &spinning(),
+        ResourceLimits {
+            fuel: Some(100_000),
+            ..ResourceLimits::default()
+        },
+    );
+    match renderer.render(b"throw", &RenderOptions::default()) {
+        Err(RenderError::Timeout(reason)) => assert_eq!(reason, "fuel exhausted"),
+        other => panic!("expected a timeout, got {other:?}"),
+    }
+    // fuel はレンダリングごとに補充される
+    renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+}
+
+#[test]
+fn epoch_deadline_is_a_timeout() {
+    let mut renderer = limited(
+        &spinning(),
+        ResourceLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..ResourceLimits::default()
+        },
+    );
+    match renderer.render(b"throw", &RenderOptions::default()) {
+        Err(RenderError::Timeout(reason)) => assert_eq!(reason, "deadline exceeded"),
+        other => panic!("expected a timeout, got {other:?}"),
+    }
+    renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+}
+
+#[test]
+fn memory_growth_beyond_max_memory_is_a_memory_limit() {
+    let max_memory = 1024 * 1024;
+    let mut renderer = limited(
+        STUB,
+        ResourceLimits {
+            max_memory: Some(max_memory),
+            ..ResourceLimits::default()
+        },
+    );
+    // 1024x1024 のピクセルデータは 4 MiB
+    let large = RenderOptions {
+        fit_to: FitTo::Size(1024, 1024),
+        ..RenderOptions::default()
+    };
+    match renderer.render_pixmap(b"<svg/>", &large) {
+        Err(RenderError::MemoryLimit {
+            resource,
+            limit,
+            requested,
+        }) => {
+            assert_eq!((resource, limit), ("memory", max_memory));
+            assert!(requested > 4 * 1024 * 1024, "{requested}");
+        }
+        other => panic!("expected a memory limit, got {other:?}"),
+    }
+
+    // 上限に収まる大きさなら描ける
+    let small = RenderOptions {
+        fit_to: FitTo::Size(256, 256),
+        ..RenderOptions::default()
+    };
+    let pixmap = renderer.render_pixmap(b"<svg/>", &small).unwrap();
+    assert_eq!((pixmap.width, pixmap.height), (256, 256));
+}
+
+/// `resvg-wasm render` を `wat` のゲストで実行し、終了コードを返す。
+fn cli_exit_code(dir: &Path, wat: &str, svg: &str, args: &[&str]) -> i32 {
+    let wasm = dir.join("guest.wat");
+    let input = dir.join("input.svg");
+    std::fs::write(&wasm, wat).unwrap();
+    std::fs::write(&input, svg).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_resvg-wasm"))
+        .arg("--wasm")
+        .arg(&wasm)
+        .arg("--no-cache")
+        .arg("render")
+        .arg(&input)
+        .arg("-o")
+        .arg(dir.join("output.ppm"))
+        .args(args)
+        .env_remove("RESVG_WASM_LOG")
+        .output()
+        .unwrap();
+    output.status.code().expect("exited normally")
+}
+
+#[test]
+fn cli_exit_codes_distinguish_error_kinds() {
+    let dir = std::env::temp_dir().join(format!("resvg-wasm-exit-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    assert_eq!(cli_exit_code(&dir, STUB, "<svg/>", &[]), 0);
+    // ホストとの ABI の食い違い
+    let no_render = STUB.replace("(export \"context_render\")", "");
+    assert_eq!(cli_exit_code(&dir, &no_render, "<svg/>", &[]), 4);
+    // ゲストのエラー
+    assert_eq!(cli_exit_code(&dir, STUB, "throw", &[]), 5);
+    // fuel・タイムアウト・メモリの上限
+    assert_eq!(
+        cli_exit_code(&dir, &spinning(), "throw", &["--fuel", "100000"]),
+        6
+    );
+    assert_eq!(
+        cli_exit_code(&dir, &spinning(), "throw", &["--timeout", "0.05"]),
+        6
+    );
+    assert_eq!(
+        cli_exit_code(
+            &dir,
+            STUB,
+            "<svg/>",
+            &["--max-memory", "1", "--width", "1024", "--height", "1024"]
+        ),
+        6
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_compare_exits_with_its_own_code_on_regressions() {
+    let dir = std::env::temp_dir().join(format!("resvg-wasm-compare-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let wasm = dir.join("guest.wat");
+    let input = dir.join("input.svg");
+    let reference = dir.join("reference.png");
+    std::fs::write(&wasm, STUB).unwrap();
+    std::fs::write(&input, "<svg/>").unwrap();
+
+    let compare = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_resvg-wasm"))
+            .arg("--wasm")
+            .arg(&wasm)
+            .arg("--no-cache")
+            .arg("compare")
+            .arg(&input)
+            .arg("--reference")
+            .arg(&reference)
+            .args(args)
+            .env_remove("RESVG_WASM_LOG")
+            .output()
+            .unwrap()
+            .status
+            .code()
+            .expect("exited normally")
+    };
+
+    // 基準画像を作れば一致する
+    assert_eq!(compare(&["--update"]), 0);
+    assert_eq!(compare(&[]), 0);
+
+    // ピクセルが違う
+    let mut image = image::open(&reference).unwrap().into_rgba8();
+    image
+        .pixels_mut()
+        .for_each(|p| p.0 = [255 - p.0[0], 0, 0, 255]);
+    image.save(&reference).unwrap();
+    assert_eq!(compare(&["--threshold", "0"]), 9);
+    assert!(dir.join("reference.diff.png").exists());
+    assert_eq!(compare(&["--threshold", "1"]), 0);
+
+    // 大きさが違う
+    image::RgbaImage::new(2, 2).save(&reference).unwrap();
+    assert_eq!(compare(&[]), 9);
+
+    // 比較できないのはリグレッションではない
+    std::fs::remove_file(&reference).unwrap();
+    assert_eq!(compare(&[]), 1);
+    assert_eq!(compare(&["--threshold", "2"]), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
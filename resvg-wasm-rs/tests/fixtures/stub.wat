@@ -0,0 +1,103 @@
+;; テスト用のゲスト。resvg_wasm.wasm と同じ ABI を持つが、SVG は解釈しない。
+;;
+;; - PNG 出力: 埋め込みの 1x1 PNG を返す
+;; - ピクセル出力: arg5 x arg7 (0 なら 1) を背景色 arg8 (0xRRGGBBAA) で塗りつぶす
+;; - SVG が "echo" のときは、arg3..arg10 を 1 ピクセル 1 引数 (リトルエンディアンの u32、
+;;   arg6 は 1000 倍して切り捨て) で並べた 8x1 のピクセルデータを返す
+;; - SVG が "throw" のときは __wbindgen_throw で "stub error" を投げる
+(module
+  (import "__wbindgen_placeholder__" "__wbindgen_throw" (func $throw (param i32 i32)))
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 65536))
+  ;; context_new でパースした SVG が "echo" だったか
+  (global $context_echo (mut i32) (i32.const 0))
+  (data (i32.const 256) "stub error")
+  (data (i32.const 1024) "\89\50\4e\47\0d\0a\1a\0a\00\00\00\0d\49\48\44\52\00\00\00\01\00\00\00\01\08\06\00\00\00\1f\15\c4\89\00\00\00\0d\49\44\41\54\78\9c\63\f8\cf\c0\f0\1f\00\05\00\01\ff\89\99\3d\1d\00\00\00\00\49\45\4e\44\ae\42\60\82")
+
+  ;; 必要に応じてメモリを伸ばすバンプアロケータ
+  (func $alloc (param $size i32) (result i32)
+    (local $p i32) (local $end i32)
+    (local.set $p (global.get $heap))
+    (local.set $end (i32.add (local.get $p) (local.get $size)))
+    (if (i32.gt_u (local.get $end) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (if (i32.lt_s
+              (memory.grow (i32.add (i32.shr_u (i32.sub (local.get $end) (i32.mul (memory.size) (i32.const 65536))) (i32.const 16)) (i32.const 1)))
+              (i32.const 0))
+          (then unreachable))))
+    (global.set $heap (local.get $end))
+    (local.get $p))
+  (func (export "__wbindgen_malloc") (param $size i32) (param $align i32) (result i32)
+    (call $alloc (local.get $size)))
+  (func (export "__wbindgen_free") (param i32 i32 i32))
+
+  (func $is (param $ptr i32) (param $len i32) (param $word i32) (param $word_len i32) (result i32)
+    (i32.and
+      (i32.eq (local.get $len) (local.get $word_len))
+      (i32.eq (i32.load (local.get $ptr)) (local.get $word))))
+  ;; "echo" / "thro" をリトルエンディアンで読んだ値
+  (func $is_echo (param $ptr i32) (param $len i32) (result i32)
+    (call $is (local.get $ptr) (local.get $len) (i32.const 0x6f686365) (i32.const 4)))
+  (func $is_throw (param $ptr i32) (param $len i32) (result i32)
+    (call $is (local.get $ptr) (local.get $len) (i32.const 0x6f726874) (i32.const 5)))
+
+  (func (export "context_new") (param $ptr i32) (param $len i32) (param i32 i32) (result i32)
+    (global.set $context_echo (call $is_echo (local.get $ptr) (local.get $len)))
+    (i32.const 1))
+  (func (export "context_free") (param i32))
+
+  (func (export "context_render")
+    (param $svg i32) (param $svg_len i32) (param $ctx i32) (param $mode i32) (param $width i32)
+    (param $zoom f64) (param $height i32) (param $bg i32) (param $dpi i32) (param $font_size i32)
+    (result i32)
+    (local $w i32) (local $h i32) (local $len i32) (local $out i32) (local $i i32) (local $echo i32)
+    (if (call $is_throw (local.get $svg) (local.get $svg_len))
+      (then (call $throw (i32.const 256) (i32.const 10))))
+    (local.set $echo
+      (if (result i32) (local.get $ctx)
+        (then (global.get $context_echo))
+        (else (call $is_echo (local.get $svg) (local.get $svg_len)))))
+
+    ;; PNG 出力
+    (if (i32.eqz (i32.and (local.get $mode) (i32.const 0x100)))
+      (then
+        (i32.store (i32.const 512) (i32.const 1024))
+        (i32.store (i32.const 516) (i32.const 70))
+        (return (i32.const 512))))
+
+    (if (local.get $echo)
+      (then
+        (local.set $out (call $alloc (i32.const 32)))
+        (i32.store offset=0 (local.get $out) (local.get $ctx))
+        (i32.store offset=4 (local.get $out) (local.get $mode))
+        (i32.store offset=8 (local.get $out) (local.get $width))
+        (i32.store offset=12 (local.get $out) (i32.trunc_f64_s (f64.mul (local.get $zoom) (f64.const 1000))))
+        (i32.store offset=16 (local.get $out) (local.get $height))
+        (i32.store offset=20 (local.get $out) (local.get $bg))
+        (i32.store offset=24 (local.get $out) (local.get $dpi))
+        (i32.store offset=28 (local.get $out) (local.get $font_size))
+        (local.set $w (i32.const 8))
+        (local.set $h (i32.const 1))
+        (local.set $len (i32.const 32)))
+      (else
+        (local.set $w (select (local.get $width) (i32.const 1) (local.get $width)))
+        (local.set $h (select (local.get $height) (i32.const 1) (local.get $height)))
+        (local.set $len (i32.mul (i32.mul (local.get $w) (local.get $h)) (i32.const 4)))
+        (local.set $out (call $alloc (local.get $len)))
+        (block $done
+          (loop $fill
+            (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+            ;; 0xRRGGBBAA をバイト順 R, G, B, A で書く
+            (i32.store8 offset=0 (i32.add (local.get $out) (local.get $i)) (i32.shr_u (local.get $bg) (i32.const 24)))
+            (i32.store8 offset=1 (i32.add (local.get $out) (local.get $i)) (i32.shr_u (local.get $bg) (i32.const 16)))
+            (i32.store8 offset=2 (i32.add (local.get $out) (local.get $i)) (i32.shr_u (local.get $bg) (i32.const 8)))
+            (i32.store8 offset=3 (i32.add (local.get $out) (local.get $i)) (local.get $bg))
+            (local.set $i (i32.add (local.get $i) (i32.const 4)))
+            (br $fill)))))
+
+    (i32.store (i32.const 512) (local.get $out))
+    (i32.store (i32.const 516) (local.get $len))
+    (i32.store (i32.const 520) (local.get $w))
+    (i32.store (i32.const 524) (local.get $h))
+    (i32.const 512))
+)
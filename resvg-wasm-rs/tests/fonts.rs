@@ -0,0 +1,170 @@
+//! フォントの読み込み、ゲストへの登録、ファミリの解決のテスト。
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use resvg_wasm::fonts::FontDatabase;
+use resvg_wasm::{RenderError, RenderOptions, Renderer, RendererConfig};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// ファミリ名 "Fixture Sans" の 1 フェイスだけの TrueType フォント。
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/fonts/fixture.ttf"
+);
+
+/// テストごとの一時ディレクトリ。drop で消す。
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("resvg-wasm-fonts-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn copy_fixture(&self, name: &str) -> PathBuf {
+        let path = self.0.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::copy(FIXTURE, &path).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn fixture_fonts() -> FontDatabase {
+    let mut fonts = FontDatabase::new();
+    assert_eq!(fonts.load_file(FIXTURE).unwrap(), 1);
+    fonts
+}
+
+/// stub に `body` を本体とするフォント登録関数 `font_register` を足す。
+fn stub_with_register(body: &str) -> String {
+    STUB.replace(
+        "(func (export \"context_free\") (param i32))",
+        &format!(
+            "(func (export \"context_free\") (param i32))\n  \
+             (func (export \"font_register\") (param $ptr i32) (param $len i32) (result i32)\n    \
+             {body})"
+        ),
+    )
+}
+
+fn render_with(wasm: &str, fonts: FontDatabase) -> Result<(), RenderError> {
+    let config = RendererConfig {
+        fonts: Some(Arc::new(fonts)),
+        ..RendererConfig::default()
+    };
+    let mut renderer = Renderer::from_bytes_with(wasm.as_bytes(), &config)?;
+    renderer.render(b"<svg/>", &RenderOptions::default())?;
+    Ok(())
+}
+
+#[test]
+fn font_file_families_are_read() {
+    let fonts = fixture_fonts();
+    assert_eq!(fonts.sources().len(), 1);
+    assert_eq!(fonts.sources()[0].path, Path::new(FIXTURE));
+    assert_eq!(
+        fonts.families().into_iter().collect::<Vec<_>>(),
+        ["Fixture Sans"]
+    );
+
+    let dir = TempDir::new("not-a-font");
+    let path = dir.0.join("broken.ttf");
+    std::fs::write(&path, b"not a font").unwrap();
+    assert!(matches!(
+        FontDatabase::new().load_file(&path),
+        Err(RenderError::InvalidOptions(_))
+    ));
+}
+
+#[test]
+fn load_dir_finds_fonts_recursively() {
+    let dir = TempDir::new("dir");
+    dir.copy_fixture("a.ttf");
+    dir.copy_fixture("nested/b.OTF");
+    // フォントでないファイルや拡張子の違うファイルは無視する
+    std::fs::write(dir.0.join("nested/broken.ttf"), b"not a font").unwrap();
+    std::fs::write(dir.0.join("readme.txt"), b"fonts").unwrap();
+
+    let mut fonts = FontDatabase::new();
+    assert_eq!(fonts.load_dir(&dir.0).unwrap(), 2);
+    assert_eq!(fonts.sources().len(), 2);
+    assert!(FontDatabase::new().load_dir(dir.0.join("missing")).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn load_dir_stops_at_symlink_loops() {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new("symlink");
+    dir.copy_fixture("sub/a.ttf");
+    // 親を指すループと、同じディレクトリへの別名
+    symlink(&dir.0, dir.0.join("sub/loop")).unwrap();
+    symlink(dir.0.join("sub"), dir.0.join("alias")).unwrap();
+
+    let mut fonts = FontDatabase::new();
+    assert_eq!(fonts.load_dir(&dir.0).unwrap(), 1);
+    assert_eq!(fonts.sources().len(), 1);
+}
+
+#[test]
+fn fonts_are_registered_with_the_guest() {
+    // ゲストがフォントファイルの中身をそのまま受け取ったかを、長さと先頭の sfnt の版で確かめる
+    let len = std::fs::metadata(FIXTURE).unwrap().len();
+    let wasm = stub_with_register(&format!(
+        "(if (result i32) (i32.and (i32.eq (local.get $len) (i32.const {len})) \
+         (i32.eq (i32.load (local.get $ptr)) (i32.const 0x100))) \
+         (then (i32.const 1)) (else (i32.const -1)))"
+    ));
+    render_with(&wasm, fixture_fonts()).unwrap();
+
+    // 負の戻り値は登録の失敗
+    let err = render_with(&stub_with_register("(i32.const -1)"), fixture_fonts()).unwrap_err();
+    assert!(
+        matches!(err, RenderError::Guest(ref message) if message.contains("fixture.ttf")),
+        "{err:?}"
+    );
+
+    // フォントを渡すのに登録関数がなければエラー
+    let err = render_with(STUB, fixture_fonts()).unwrap_err();
+    assert!(matches!(err, RenderError::MissingExport { .. }), "{err:?}");
+
+    // フォントがなければ登録関数は要らない
+    render_with(STUB, FontDatabase::new()).unwrap();
+}
+
+#[test]
+fn referenced_families_are_resolved_against_loaded_fonts() {
+    let svg = br#"<svg>
+        <style>.a { font-family: 'Fixture Sans', Missing One; }</style>
+        <text font-family="&quot;fixture sans&quot;, serif">a</text>
+        <text style="font-family:Missing Two;font-size:12">b</text>
+        <text font-family='inherit'>c</text>
+    </svg>"#;
+
+    let report = fixture_fonts().resolve(svg);
+    assert_eq!(
+        report.resolved.into_iter().collect::<Vec<_>>(),
+        ["Fixture Sans", "fixture sans", "serif"]
+    );
+    assert_eq!(
+        report.missing.into_iter().collect::<Vec<_>>(),
+        ["Missing One", "Missing Two"]
+    );
+
+    // フォントが 1 つもなければ総称ファミリも解決できない
+    let report = FontDatabase::new().resolve(svg);
+    assert!(report.resolved.is_empty());
+    assert!(report.missing.contains("serif"));
+}
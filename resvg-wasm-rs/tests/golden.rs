@@ -0,0 +1,125 @@
+//! レンダリング結果を保存済みの PNG と比べるスナップショットテスト。
+//!
+//! `tests/golden/<suite>/*.svg` をレンダリングし、同じ名前の `.png` とピクセル単位で比べる。
+//! SVG の先頭の `<!-- options: width=32&background=%23fff -->` はレンダリングオプション
+//! (`serve` の `/render` と同じクエリ文字列の形式) として使う。
+//!
+//! - `stub`: `tests/fixtures/stub.wat` で常に実行する。stub は SVG を解釈せずに背景色で
+//!   塗りつぶすだけなので、確かめているのはオプションの受け渡しからピクセルデータの受け取り、
+//!   ホスト側の PNG エンコード、比較までの経路で、resvg の描画結果ではない
+//!
+//! resvg の描画そのものは、本物の resvg_wasm.wasm でレンダリングした `.png` を持つスイートを
+//! 足さない限り確かめられない。
+//!
+//! `RESVG_WASM_BLESS=1` を付けて実行すると、比較せずに `.png` を書き直す。
+//! 食い違った場合は、レンダリング結果と差分画像を `target/tmp/golden/` に書き出す。
+
+use std::path::{Path, PathBuf};
+
+use resvg_wasm::diff::{self, DiffOptions};
+use resvg_wasm::serve::options_from_query;
+use resvg_wasm::{EncodeOptions, Renderer};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// 環境による丸め誤差は許す
+const DIFF: DiffOptions = DiffOptions {
+    threshold: 2.0 / 255.0,
+    max_diff_pixels: 0,
+};
+
+#[test]
+fn stub_output_plumbing() {
+    let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+    // stub.wat は PNG を作れないので、ピクセルデータをホストでエンコードする
+    let encode = EncodeOptions {
+        png_compression: Some(6),
+        ..EncodeOptions::default()
+    };
+    run_suite(&mut renderer, "stub", &encode);
+}
+
+fn run_suite(renderer: &mut Renderer, suite: &str, encode: &EncodeOptions) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(suite);
+    let bless = std::env::var_os("RESVG_WASM_BLESS").is_some_and(|v| v != "0");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("golden")
+        .join(suite);
+
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "svg"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no SVGs in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for svg_path in &cases {
+        let name = svg_path.file_stem().unwrap().to_string_lossy().into_owned();
+        let svg = std::fs::read(svg_path).unwrap();
+        let (opts, _) = options_from_query(&options_comment(&svg)).unwrap();
+        let image = match renderer.render_image(&svg, &opts, encode) {
+            Ok(image) => image,
+            Err(err) => {
+                failures.push(format!("{name}: render failed: {err:#}"));
+                continue;
+            }
+        };
+
+        let golden = svg_path.with_extension("png");
+        if bless {
+            std::fs::write(&golden, &image.data).unwrap();
+            continue;
+        }
+        let Ok(expected) = std::fs::read(&golden) else {
+            failures.push(format!(
+                "{name}: missing {} (run with RESVG_WASM_BLESS=1 to create it)",
+                golden.display()
+            ));
+            continue;
+        };
+
+        let report = diff::compare(&image.data, &expected, &DIFF).unwrap();
+        if report.passed() {
+            continue;
+        }
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let actual = out_dir.join(format!("{name}.actual.png"));
+        std::fs::write(&actual, &image.data).unwrap();
+        if let Some(diff) = &report.diff_image {
+            std::fs::write(out_dir.join(format!("{name}.diff.png")), &diff.data).unwrap();
+        }
+        failures.push(format!(
+            "{name}: rendered {}x{}, golden {}x{}, {} pixels differ (max delta {:.3}); see {}",
+            report.width,
+            report.height,
+            report.reference_size.0,
+            report.reference_size.1,
+            report.mismatched,
+            report.max_delta,
+            actual.display()
+        ));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} golden images in {suite} failed:\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
+
+/// 先頭の `<!-- options: ... -->` の中身。なければ空文字列。
+fn options_comment(svg: &[u8]) -> String {
+    let text = String::from_utf8_lossy(svg);
+    text.trim_start()
+        .strip_prefix("<!--")
+        .and_then(|rest| rest.split_once("-->"))
+        .and_then(|(comment, _)| comment.trim().strip_prefix("options:"))
+        .map(|query| query.trim().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,199 @@
+//! アイコンの書き出し (favicon.ico、iconset) のテスト。
+
+use std::process::Command;
+
+use resvg_wasm::icons::{self, Rendered, Variant, ICONSET_SIZES, ICO_SIZES};
+use resvg_wasm::{EncodeOptions, ImageFormat, RenderError, RenderOptions, Renderer};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// stub で `sizes` の正方形の PNG をホスト側でエンコードしてレンダリングする。
+fn render_sizes(sizes: &[u32]) -> Vec<Rendered> {
+    let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+    let encode = EncodeOptions {
+        png_compression: Some(6),
+        ..EncodeOptions::default()
+    };
+    let variants: Vec<Variant> = sizes.iter().map(|&n| Variant::Size(n)).collect();
+    icons::render_variants(
+        &mut renderer,
+        b"<svg/>",
+        &RenderOptions::default(),
+        &encode,
+        &variants,
+    )
+    .unwrap()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn ico_directory_describes_every_image() {
+    let rendered = render_sizes(ICO_SIZES);
+    let images: Vec<_> = rendered.iter().map(|r| &r.image).collect();
+    let ico = icons::write_ico(&images).unwrap();
+
+    // ICONDIR: 予約 0, 種類 1 (アイコン), 枚数
+    assert_eq!(u16_at(&ico, 0), 0);
+    assert_eq!(u16_at(&ico, 2), 1);
+    assert_eq!(u16_at(&ico, 4) as usize, ICO_SIZES.len());
+
+    let mut expected_offset = 6 + 16 * ICO_SIZES.len();
+    for (i, (&size, image)) in ICO_SIZES.iter().zip(&images).enumerate() {
+        let entry = &ico[6 + 16 * i..6 + 16 * (i + 1)];
+        assert_eq!((image.width, image.height), (size, size));
+        // 256px は 0 で表す
+        let encoded = if size == 256 { 0 } else { size as u8 };
+        assert_eq!((entry[0], entry[1]), (encoded, encoded), "{size}px");
+        assert_eq!((entry[2], entry[3]), (0, 0));
+        assert_eq!((u16_at(entry, 4), u16_at(entry, 6)), (1, 32));
+
+        let len = u32_at(entry, 8) as usize;
+        let offset = u32_at(entry, 12) as usize;
+        assert_eq!(len, image.data.len(), "{size}px");
+        assert_eq!(offset, expected_offset, "{size}px");
+        assert_eq!(&ico[offset..offset + len], image.data, "{size}px");
+        assert!(ico[offset..].starts_with(b"\x89PNG\r\n\x1a\n"));
+        expected_offset += len;
+    }
+    assert_eq!(ico.len(), expected_offset);
+}
+
+#[test]
+fn ico_rejects_images_it_cannot_hold() {
+    let rendered = render_sizes(&[257]);
+    let Err(err) = icons::write_ico(&[&rendered[0].image]) else {
+        panic!("a 257px image was accepted");
+    };
+    assert!(matches!(err, RenderError::InvalidOptions(_)), "{err:?}");
+
+    let mut jpeg = render_sizes(&[16]).remove(0).image;
+    jpeg.format = ImageFormat::Jpeg;
+    assert!(matches!(
+        icons::write_ico(&[&jpeg]),
+        Err(RenderError::InvalidOptions(_))
+    ));
+    assert!(matches!(
+        icons::write_ico(&[]),
+        Err(RenderError::InvalidOptions(_))
+    ));
+}
+
+#[test]
+fn iconset_uses_apple_file_names() {
+    let dir = std::env::temp_dir().join(format!("resvg-wasm-iconset-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let rendered = render_sizes(ICONSET_SIZES);
+    let iconset = icons::write_iconset(&dir.join("app.iconset"), &rendered).unwrap();
+    assert!(iconset.skipped.is_empty(), "{:?}", iconset.skipped);
+    let names: Vec<String> = iconset
+        .written
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "icon_128x128.png",
+            "icon_128x128@2x.png",
+            "icon_16x16.png",
+            "icon_16x16@2x.png",
+            "icon_256x256.png",
+            "icon_256x256@2x.png",
+            "icon_32x32.png",
+            "icon_32x32@2x.png",
+            "icon_512x512.png",
+            "icon_512x512@2x.png",
+        ]
+    );
+    // @2x は倍の大きさの画像
+    let at_2x = std::fs::read(dir.join("app.iconset/icon_16x16@2x.png")).unwrap();
+    let expected = rendered.iter().find(|r| r.image.width == 32).unwrap();
+    assert_eq!(at_2x, expected.image.data);
+
+    // iconset に使えるサイズがなければエラー
+    let Err(err) = icons::write_iconset(&dir.join("odd.iconset"), &render_sizes(&[48])) else {
+        panic!("a 48px-only iconset was written");
+    };
+    assert!(matches!(err, RenderError::InvalidOptions(_)), "{err:?}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn iconset_reports_skipped_images() {
+    let dir =
+        std::env::temp_dir().join(format!("resvg-wasm-iconset-skipped-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut rendered = render_sizes(&[16, 32, 48, 64]);
+    // 横長の SVG を --sizes 64 でレンダリングした場合と同じ
+    rendered[3].image.height = 32;
+    let iconset = icons::write_iconset(&dir, &rendered).unwrap();
+    assert_eq!(iconset.written.len(), 3);
+    assert_eq!(
+        iconset.skipped,
+        [
+            (
+                Variant::Size(48),
+                "48x48 is not an iconset size".to_string()
+            ),
+            (Variant::Size(64), "64x32 is not square".to_string()),
+        ]
+    );
+    assert!(!dir.join("icon_32x32@2x.png").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn export_rejects_a_template_that_overwrites_variants() {
+    let dir = std::env::temp_dir().join(format!("resvg-wasm-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let wasm = dir.join("guest.wat");
+    let input = dir.join("logo.svg");
+    std::fs::write(&wasm, STUB).unwrap();
+    std::fs::write(&input, "<svg/>").unwrap();
+
+    let export = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_resvg-wasm"))
+            .arg("--wasm")
+            .arg(&wasm)
+            .arg("--no-cache")
+            .arg("export")
+            .arg(&input)
+            .args(args)
+            .env_remove("RESVG_WASM_LOG")
+            .output()
+            .unwrap()
+            .status
+            .code()
+            .expect("exited normally")
+    };
+    let template = |name: &str| dir.join(name).display().to_string();
+
+    assert_eq!(
+        export(&["--sizes", "16,32", "-o", &template("logo.png")]),
+        2
+    );
+    assert_eq!(
+        export(&["--scales", "1,2", "-o", &template("logo-{width}.png")]),
+        2
+    );
+    assert!(!dir.join("logo.png").exists());
+
+    // 1 つだけなら、またはバリエーションごとに名前が変わるなら書き出す
+    assert_eq!(export(&["--sizes", "16", "-o", &template("logo.png")]), 0);
+    assert_eq!(
+        export(&["--sizes", "16,32", "-o", &template("logo-{size}.png")]),
+        0
+    );
+    assert!(dir.join("logo-16.png").exists() && dir.join("logo-32.png").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
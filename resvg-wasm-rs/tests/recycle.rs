@@ -0,0 +1,98 @@
+//! ゲストのメモリの監視とインスタンスの作り直しのテスト。
+//!
+//! stub.wat のアロケータは解放しても再利用しないので、レンダリングのたびにメモリが伸びる。
+
+use resvg_wasm::{FitTo, RecyclePolicy, RenderOptions, Renderer, RendererConfig};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+fn stub(recycle: RecyclePolicy) -> Renderer {
+    let config = RendererConfig {
+        recycle,
+        ..RendererConfig::default()
+    };
+    Renderer::from_bytes_with(STUB.as_bytes(), &config).unwrap()
+}
+
+/// 512x512 (1 MiB) のピクセルデータ
+fn large() -> RenderOptions {
+    RenderOptions {
+        fit_to: FitTo::Size(512, 512),
+        ..RenderOptions::default()
+    }
+}
+
+#[test]
+fn memory_growth_is_tracked() {
+    let mut renderer = stub(RecyclePolicy::default());
+    let before = renderer.memory_stats();
+    assert_eq!(before.current, before.baseline);
+    assert_eq!(before.renders, 0);
+
+    for _ in 0..3 {
+        renderer.render_pixmap(b"<svg/>", &large()).unwrap();
+    }
+    let after = renderer.memory_stats();
+    assert_eq!(
+        (after.renders, after.total_renders, after.recycles),
+        (3, 3, 0)
+    );
+    assert!(after.growth() >= 3 * 512 * 512 * 4, "{after:?}");
+    assert_eq!(after.peak, after.current);
+}
+
+#[test]
+fn instance_is_recycled_after_n_renders() {
+    let mut renderer = stub(RecyclePolicy {
+        max_renders: Some(2),
+        ..RecyclePolicy::default()
+    });
+    for _ in 0..5 {
+        renderer
+            .render(b"<svg/>", &RenderOptions::default())
+            .unwrap();
+    }
+    let stats = renderer.memory_stats();
+    assert_eq!(stats.total_renders, 5);
+    assert_eq!(stats.recycles, 2);
+    assert_eq!(stats.renders, 1);
+}
+
+#[test]
+fn instance_is_recycled_when_memory_grows() {
+    let mut renderer = stub(RecyclePolicy {
+        max_memory_growth: Some(2 * 1024 * 1024),
+        ..RecyclePolicy::default()
+    });
+    renderer.render_pixmap(b"<svg/>", &large()).unwrap();
+    assert_eq!(renderer.memory_stats().recycles, 0);
+    renderer.render_pixmap(b"<svg/>", &large()).unwrap();
+
+    // 捨てたインスタンスのメモリは数えず、最大値だけが残る
+    let stats = renderer.memory_stats();
+    assert_eq!(stats.recycles, 1);
+    assert_eq!((stats.current, stats.renders), (0, 0));
+    assert!(stats.peak >= 2 * 1024 * 1024, "{stats:?}");
+
+    // 次のレンダリングは新しいインスタンスで行う
+    renderer.render_pixmap(b"<svg/>", &large()).unwrap();
+    let stats = renderer.memory_stats();
+    assert_eq!((stats.renders, stats.total_renders), (1, 3));
+    assert!(stats.growth() < 2 * 1024 * 1024, "{stats:?}");
+}
+
+#[test]
+fn parsed_context_survives_recycling() {
+    let mut renderer = stub(RecyclePolicy {
+        max_renders: Some(1),
+        ..RecyclePolicy::default()
+    });
+    let mut context = renderer
+        .parse(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+    for _ in 0..3 {
+        context.render(&RenderOptions::default()).unwrap();
+    }
+    drop(context);
+    assert_eq!(renderer.memory_stats().recycles, 3);
+}
@@ -0,0 +1,188 @@
+//! 外部リソースのリゾルバのテスト。
+
+use std::path::PathBuf;
+
+use resvg_wasm::resource::{DataUriResolver, DenyAll, FsResolver, ResourceResolver};
+use resvg_wasm::RenderError;
+
+/// `root/` と、その外の `outside/` を持つ一時ディレクトリ。
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("resvg-wasm-resource-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("root/images")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        std::fs::write(dir.join("root/images/a b.png"), b"inside").unwrap();
+        std::fs::write(dir.join("outside/secret.txt"), b"secret").unwrap();
+        Sandbox { dir }
+    }
+
+    fn root(&self) -> PathBuf {
+        self.dir.join("root")
+    }
+
+    fn resolver(&self) -> FsResolver {
+        FsResolver::new(self.root()).unwrap()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn relative_paths_inside_the_root_are_read() {
+    let sandbox = Sandbox::new("relative");
+    let resolver = sandbox.resolver();
+    assert_eq!(
+        resolver.root(),
+        sandbox.root().canonicalize().unwrap().as_path()
+    );
+    assert_eq!(resolver.resolve("images/a%20b.png").unwrap(), b"inside");
+    assert_eq!(
+        resolver.resolve("./images/../images/a b.png").unwrap(),
+        b"inside"
+    );
+}
+
+#[test]
+fn parent_traversal_is_rejected() {
+    let sandbox = Sandbox::new("traversal");
+    let resolver = sandbox.resolver();
+    assert_eq!(resolver.resolve("../outside/secret.txt"), None);
+    assert_eq!(resolver.resolve("images/../../outside/secret.txt"), None);
+    assert_eq!(resolver.resolve("%2e%2e/outside/secret.txt"), None);
+}
+
+#[test]
+fn absolute_paths_do_not_escape_the_root() {
+    let sandbox = Sandbox::new("absolute");
+    let resolver = sandbox.resolver();
+    let outside = sandbox.dir.join("outside/secret.txt");
+    assert_eq!(resolver.resolve(&outside.display().to_string()), None);
+    assert_eq!(resolver.resolve("/etc/passwd"), None);
+    // 絶対パスは root からのパスとして扱う
+    assert_eq!(resolver.resolve("/images/a b.png").unwrap(), b"inside");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_checked_after_canonicalizing() {
+    use std::os::unix::fs::symlink;
+
+    let sandbox = Sandbox::new("symlink");
+    let root = sandbox.root();
+    symlink(
+        sandbox.dir.join("outside/secret.txt"),
+        root.join("secret.txt"),
+    )
+    .unwrap();
+    symlink(sandbox.dir.join("outside"), root.join("linked")).unwrap();
+    symlink(root.join("images/a b.png"), root.join("alias.png")).unwrap();
+
+    let resolver = sandbox.resolver();
+    assert_eq!(resolver.resolve("secret.txt"), None);
+    assert_eq!(resolver.resolve("linked/secret.txt"), None);
+    assert_eq!(resolver.resolve("alias.png").unwrap(), b"inside");
+}
+
+#[test]
+fn file_uris_are_resolved_from_the_root() {
+    let sandbox = Sandbox::new("file-uri");
+    let resolver = sandbox.resolver();
+    assert_eq!(
+        resolver.resolve("file:///images/a%20b.png").unwrap(),
+        b"inside"
+    );
+    assert_eq!(
+        resolver.resolve("file://images/a%20b.png").unwrap(),
+        b"inside"
+    );
+    assert_eq!(resolver.resolve("file:///../outside/secret.txt"), None);
+    let outside = sandbox.dir.join("outside/secret.txt");
+    assert_eq!(
+        resolver.resolve(&format!("file://{}", outside.display())),
+        None
+    );
+}
+
+#[test]
+fn other_schemes_and_directories_are_not_resolved() {
+    let sandbox = Sandbox::new("schemes");
+    let resolver = sandbox.resolver();
+    assert_eq!(resolver.resolve("https://example.com/a.png"), None);
+    assert_eq!(resolver.resolve("images"), None);
+    assert_eq!(resolver.resolve(""), None);
+}
+
+#[test]
+fn missing_files_and_roots() {
+    let sandbox = Sandbox::new("missing");
+    let resolver = sandbox.resolver();
+    assert_eq!(resolver.resolve("images/missing.png"), None);
+    // 不正なパーセントエンコーディング
+    assert_eq!(resolver.resolve("images/a%2"), None);
+
+    match FsResolver::new(sandbox.dir.join("no-such-dir")) {
+        Err(RenderError::InvalidOptions(message)) => {
+            assert!(message.starts_with("invalid resource root "), "{message}")
+        }
+        other => panic!("expected an invalid-options error, got {other:?}"),
+    }
+    match FsResolver::new(sandbox.dir.join("outside/secret.txt")) {
+        Err(RenderError::InvalidOptions(message)) => {
+            assert!(message.ends_with("is not a directory"), "{message}")
+        }
+        other => panic!("expected an invalid-options error, got {other:?}"),
+    }
+}
+
+#[test]
+fn data_uris_are_decoded() {
+    let sandbox = Sandbox::new("data-uri");
+    let resolvers: [&dyn ResourceResolver; 2] = [&DataUriResolver, &sandbox.resolver()];
+    for resolver in resolvers {
+        assert_eq!(
+            resolver.resolve("data:image/png;base64,aGVsbG8=").unwrap(),
+            b"hello"
+        );
+        // 属性値に入った改行や空白、パーセントエンコードされた base64
+        assert_eq!(
+            resolver
+                .resolve("data:image/png;base64,aGVs\n  bG8%3D")
+                .unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            resolver.resolve("data:image/svg+xml,%3Csvg%2F%3E").unwrap(),
+            b"<svg/>"
+        );
+        assert_eq!(resolver.resolve("data:,").unwrap(), b"");
+        assert_eq!(resolver.resolve("data:image/png;base64,!!!"), None);
+        assert_eq!(resolver.resolve("data:image/png"), None);
+    }
+    assert_eq!(DataUriResolver.resolve("images/a%20b.png"), None);
+}
+
+#[test]
+fn deny_all_refuses_everything() {
+    let sandbox = Sandbox::new("deny");
+    let inside = sandbox.root().join("images/a b.png");
+    for href in [
+        "images/a%20b.png",
+        inside.to_str().unwrap(),
+        "file:///images/a%20b.png",
+        "data:,hello",
+        "https://example.com/a.png",
+    ] {
+        assert_eq!(DenyAll.resolve(href), None, "{href}");
+    }
+    assert!(inside.is_file());
+}
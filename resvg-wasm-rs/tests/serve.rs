@@ -0,0 +1,192 @@
+//! HTTP サーバのテスト。`tests/fixtures/stub.wat` をゲストにして、実際にポートを開いて叩く。
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::num::NonZeroUsize;
+use std::thread::JoinHandle;
+
+use resvg_wasm::serve::{RenderServer, ServeConfig, ShutdownHandle};
+use resvg_wasm::{RenderError, Renderer};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// 1 件のレスポンス。
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> serde_json::Value {
+        assert_eq!(self.header("Content-Type"), Some("application/json"));
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// 別スレッドで動かしているサーバ。drop で止める。
+struct TestServer {
+    addr: SocketAddr,
+    shutdown: ShutdownHandle,
+    thread: Option<JoinHandle<Result<(), RenderError>>>,
+}
+
+impl TestServer {
+    fn start(wat: &str, max_body: usize) -> Self {
+        let renderer = Renderer::from_bytes(wat.as_bytes()).unwrap();
+        let config = ServeConfig {
+            workers: NonZeroUsize::new(2).unwrap(),
+            max_body,
+        };
+        let server = RenderServer::bind("127.0.0.1:0", renderer, config).unwrap();
+        let addr = server.local_addr().unwrap();
+        let shutdown = server.shutdown_handle();
+        let thread = std::thread::spawn(move || server.run());
+        TestServer {
+            addr,
+            shutdown,
+            thread: Some(thread),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> Reply {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.addr,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).unwrap();
+        let split = raw
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("response has a header");
+        let head = String::from_utf8(raw[..split].to_vec()).unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap();
+        Reply {
+            status: status.parse().unwrap(),
+            headers: lines
+                .filter_map(|line| line.split_once(": "))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: raw[split + 4..].to_vec(),
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap().unwrap();
+        }
+    }
+}
+
+#[test]
+fn render_returns_the_image() {
+    let server = TestServer::start(STUB, 1024);
+    let reply = server.request("POST", "/render", b"<svg/>");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Type"), Some("image/png"));
+    assert!(reply.body.starts_with(b"\x89PNG\r\n\x1a\n"));
+    assert_eq!(reply.body.len(), 70);
+    assert_eq!(reply.header("X-Missing-Fonts"), None);
+
+    // ホストでエンコードする形式
+    let reply = server.request("POST", "/render?width=4&height=2&format=ppm", b"<svg/>");
+    assert_eq!(reply.status, 200);
+    assert_eq!(
+        reply.header("Content-Type"),
+        Some("image/x-portable-pixmap")
+    );
+    assert!(reply.body.starts_with(b"P6\n4 2"));
+}
+
+#[test]
+fn healthz_and_unknown_routes() {
+    let server = TestServer::start(STUB, 1024);
+    let reply = server.request("GET", "/healthz", b"");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.json(), serde_json::json!({ "status": "ok" }));
+
+    let reply = server.request("GET", "/render", b"");
+    assert_eq!(reply.status, 405);
+    assert_eq!(reply.json()["kind"], "method");
+
+    let reply = server.request("GET", "/nope", b"");
+    assert_eq!(reply.status, 404);
+    assert_eq!(reply.json()["kind"], "not_found");
+}
+
+#[test]
+fn oversized_body_is_rejected() {
+    let server = TestServer::start(STUB, 16);
+    let reply = server.request("POST", "/render", &[b' '; 17]);
+    assert_eq!(reply.status, 413);
+    let body = reply.json();
+    assert_eq!(body["kind"], "too_large");
+    assert_eq!(body["error"], "request body exceeds 16 bytes");
+
+    // ちょうど上限なら受け付ける
+    let reply = server.request("POST", "/render", b"<svg/>          ");
+    assert_eq!(reply.status, 200);
+}
+
+#[test]
+fn invalid_options_are_a_bad_request() {
+    let server = TestServer::start(STUB, 1024);
+    for query in ["zoom=0", "width=abc", "bogus=1", "background=%ZZ"] {
+        let reply = server.request("POST", &format!("/render?{query}"), b"<svg/>");
+        assert_eq!(reply.status, 400, "{query}");
+        assert_eq!(reply.json()["kind"], "invalid_options", "{query}");
+    }
+}
+
+#[test]
+fn guest_errors_are_reported_with_their_kind() {
+    let server = TestServer::start(STUB, 1024);
+    let reply = server.request("POST", "/render", b"throw");
+    assert_eq!(reply.status, 422);
+    assert_eq!(
+        reply.json(),
+        serde_json::json!({ "error": "stub error", "kind": "guest" })
+    );
+
+    // ワーカーはエラーの後も使える
+    let reply = server.request("POST", "/render", b"<svg/>");
+    assert_eq!(reply.status, 200);
+}
+
+#[test]
+fn traps_return_only_the_summary_line() {
+    let wat = STUB.replace(
+        "(then (call $throw (i32.const 256) (i32.const 10)))",
+        "(then unreachable)",
+    );
+    let server = TestServer::start(&wat, 1024);
+    let reply = server.request("POST", "/render", b"throw");
+    assert_eq!(reply.status, 422);
+    let body = reply.json();
+    assert_eq!(body["kind"], "trap");
+    let message = body["error"].as_str().unwrap();
+    assert!(
+        message.starts_with("guest trapped in `context_render`: "),
+        "{message}"
+    );
+    assert!(!message.contains('\n'), "{message}");
+    assert!(!message.contains("wasm backtrace"), "{message}");
+}
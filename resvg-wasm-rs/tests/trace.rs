@@ -0,0 +1,110 @@
+//! tracing の span のテスト。
+//!
+//! JSON で書き出した span の終了イベントを読み、名前とフィールドを確かめる。
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use resvg_wasm::{RenderOptions, Renderer};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// 書き込まれた JSON Lines を溜めておく
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Buffer {
+    type Writer = Buffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// `f` の間に閉じた span を `(名前, フィールド)` の順に返す。
+fn closed_spans(f: impl FnOnce()) -> Vec<(String, serde_json::Value)> {
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::TRACE)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(buffer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    output
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["fields"]["message"] == "close")
+        .map(|event| {
+            let span = event["span"].clone();
+            (span["name"].as_str().unwrap().to_string(), span)
+        })
+        .collect()
+}
+
+#[test]
+fn render_phases_are_traced_in_order() {
+    let spans = closed_spans(|| {
+        let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+        renderer
+            .render(b"<svg/>", &RenderOptions::default())
+            .unwrap();
+    });
+    let names: Vec<&str> = spans.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "link",
+            "instantiate",
+            "upload",
+            "context_render",
+            "extract_output",
+            "render"
+        ]
+    );
+
+    let (_, render) = spans.last().unwrap();
+    assert_eq!(render["svg_size"], 6);
+    assert_eq!(
+        (&render["width"], &render["height"]),
+        (&1.into(), &1.into())
+    );
+    assert_eq!(render["output_size"], 70);
+    assert!(render["memory"].as_u64().unwrap() >= 65536, "{render}");
+}
+
+#[test]
+fn context_renders_record_the_context_instead_of_the_svg() {
+    let spans = closed_spans(|| {
+        let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+        let mut context = renderer
+            .parse(b"<svg/>", &RenderOptions::default())
+            .unwrap();
+        context.render_pixmap(&RenderOptions::default()).unwrap();
+    });
+    let names: Vec<&str> = spans.iter().map(|(name, _)| name.as_str()).collect();
+    assert!(!names.contains(&"upload"), "{names:?}");
+
+    let (_, extract) = spans
+        .iter()
+        .find(|(name, _)| name == "extract_output")
+        .unwrap();
+    assert_eq!(extract["format"], "pixmap");
+    let (_, render) = spans.iter().find(|(name, _)| name == "render").unwrap();
+    assert!(render["context"].is_number(), "{render}");
+    assert!(render.get("svg_size").is_none(), "{render}");
+}
@@ -0,0 +1,82 @@
+//! ゲストのトラップのバックトレースとコアダンプのテスト。
+
+use resvg_wasm::{EngineOptions, RenderError, RenderOptions, Renderer, RendererConfig};
+
+const STUB: &str = include_str!("fixtures/stub.wat");
+
+/// SVG が "throw" のときに unreachable でトラップする stub。
+/// `context_render` には name セクションの名前を付ける
+fn trapping(engine: EngineOptions) -> Renderer {
+    let wat = STUB
+        .replace(
+            "(then (call $throw (i32.const 256) (i32.const 10)))",
+            "(then unreachable)",
+        )
+        .replace(
+            "(func (export \"context_render\")",
+            "(func $context_render (export \"context_render\")",
+        );
+    let config = RendererConfig {
+        engine,
+        ..RendererConfig::default()
+    };
+    Renderer::from_bytes_with(wat.as_bytes(), &config).unwrap()
+}
+
+fn trap(renderer: &mut Renderer) -> RenderError {
+    match renderer.render(b"throw", &RenderOptions::default()) {
+        Err(err @ RenderError::Trap { .. }) => err,
+        other => panic!("expected a trap, got {other:?}"),
+    }
+}
+
+#[test]
+fn trap_carries_a_backtrace_of_the_guest() {
+    let mut renderer = trapping(EngineOptions::default());
+    let err = trap(&mut renderer);
+
+    let backtrace = err.backtrace().expect("backtrace");
+    let names: Vec<String> = backtrace.frames.iter().map(|f| f.name()).collect();
+    assert_eq!(names.first().map(String::as_str), Some("context_render"));
+    assert!(backtrace.frames[0].module_offset.is_some());
+    // stub にはデバッグ情報がない
+    assert!(backtrace.frames.iter().all(|f| f.locations.is_empty()));
+
+    // wasmtime のバックトレースは重複させず、自前の形式で 1 回だけ出す
+    let message = err.to_string();
+    assert!(
+        message.starts_with("guest trapped in `context_render`: "),
+        "{message}"
+    );
+    assert!(message.contains("unreachable"), "{message}");
+    assert_eq!(message.matches("wasm backtrace:").count(), 1, "{message}");
+    assert!(message.contains("- context_render"), "{message}");
+    assert!(err.coredump().is_none());
+}
+
+#[test]
+fn coredump_is_captured_when_enabled() {
+    let mut renderer = trapping(EngineOptions {
+        coredump: true,
+        debug_info: true,
+        ..EngineOptions::default()
+    });
+    let err = trap(&mut renderer);
+    let coredump = err.coredump().expect("coredump");
+    assert_eq!(&coredump[..4], b"\0asm");
+
+    // トラップしたインスタンスは捨てられ、次のレンダリングは成功する
+    renderer
+        .render(b"<svg/>", &RenderOptions::default())
+        .unwrap();
+}
+
+#[test]
+fn guest_errors_have_no_backtrace() {
+    let mut renderer = Renderer::from_bytes(STUB.as_bytes()).unwrap();
+    let err = renderer
+        .render(b"throw", &RenderOptions::default())
+        .unwrap_err();
+    assert!(matches!(err, RenderError::Guest(_)), "{err:?}");
+    assert!(err.backtrace().is_none() && err.coredump().is_none());
+}